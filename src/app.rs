@@ -1,45 +1,79 @@
-use std::collections::HashMap;
+use std::path::Path;
 
-use raylib::text::Font;
+use raylib::ffi;
 
+use crate::assets::Assets;
+use crate::cli::Options;
+use crate::heatmap::run_heatmap;
+use crate::level::Level;
+use crate::paths::{resource_path, DEFAULT_SCRIPT};
+use crate::screens::game::GameScreen;
 use crate::screens::menu::MenuScreen;
 use crate::screens::Screen;
-use crate::textures::load_textures;
 
 const CELL_SIZE: i32 = 32;
 const TARGET_FPS: u32 = 120;
+const DEFAULT_HEATMAP: &str = "heatmap.png";
 
-pub fn run_app() -> Result<(), Box<dyn std::error::Error>> {
+pub fn run_app(options: Options) -> Result<(), Box<dyn std::error::Error>> {
     let (screen_width, screen_height) = (352.0, 352.0);
 
+    // The level is loaded before opening the window so a broken file fails right away
+    let level = options.level.map(Level::load).transpose()?;
+    let script_path = options
+        .script
+        .unwrap_or_else(|| resource_path(DEFAULT_SCRIPT));
+
+    if options.heatmap.is_some() || options.record.is_some() {
+        // Heatmaps and recordings are drawn offscreen, the window is only needed for the
+        // graphics context. The flag has to be set before the window opens or it shows up for a
+        // moment, raylib keeps it next to the flags of the builder
+        unsafe { ffi::SetConfigFlags(ffi::ConfigFlags::FLAG_WINDOW_HIDDEN as u32) };
+    }
+
     let (mut rl, thread) = raylib::init()
         .size(352 as i32, 352 as i32)
         .title("Rust Navigator")
         .build();
 
-    let mut fonts: HashMap<String, Font> = HashMap::new();
-    fonts.insert(
-        "geist".to_string(),
-        rl.load_font_ex(&thread, "./fonts/GeistMono-Regular.ttf", 128, None)
-            .unwrap(),
-    );
-
     rl.set_target_fps(TARGET_FPS);
 
-    let textures = load_textures(&mut rl, &thread);
+    let asset_directory = options.assets.as_deref().map(Path::new);
+    let assets = Assets::load(&mut rl, &thread, asset_directory)?;
 
-    let mut screen: Box<dyn Screen> = Box::new(MenuScreen::new());
-    screen.initialize(screen_width, screen_height, &textures, &fonts);
+    if let (Some(level), Some(directory)) = (&level, &options.heatmap) {
+        let output = options.output.as_deref().unwrap_or(DEFAULT_HEATMAP);
+        run_heatmap(&mut rl, &thread, &assets, level, directory, output)?;
+
+        return Ok(());
+    }
+
+    if let (Some(level), Some(output)) = (&level, &options.record) {
+        let screen = GameScreen::new(level.clone(), &script_path);
+        let mut d = rl.begin_drawing(&thread);
+
+        for path in screen.export(&mut d, &assets, Path::new(output))? {
+            println!("Saved the playback to {}", path.display());
+        }
+
+        return Ok(());
+    }
+
+    let mut screen: Box<dyn Screen> = match level {
+        Some(level) => Box::new(GameScreen::new(level, &script_path)),
+        None => Box::new(MenuScreen::with_script(script_path)),
+    };
+    screen.initialize(screen_width, screen_height, &assets);
 
     while !rl.window_should_close() {
         let mut d = rl.begin_drawing(&thread);
-        if screen.update(&mut d, &textures, &fonts) {
+        if screen.update(&mut d, &assets) {
             let new_screen = screen.get_new_screen();
 
             drop(screen);
 
             screen = new_screen;
-            screen.initialize(screen_width, screen_height, &textures, &fonts);
+            screen.initialize(screen_width, screen_height, &assets);
         }
     }
 
@@ -57,3 +91,8 @@ pub fn tile_to_screen_pos_centered(x: u8, y: u8) -> (i32, i32) {
         screen_pos.1 + (CELL_SIZE as f32 / 2.0).round() as i32,
     )
 }
+
+/// Returns the tile under a screen position, which may lie outside of the world
+pub fn screen_to_tile_pos(x: i32, y: i32) -> (i32, i32) {
+    (x.div_euclid(CELL_SIZE), y.div_euclid(CELL_SIZE))
+}
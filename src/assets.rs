@@ -0,0 +1,343 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use raylib::{
+    color::Color,
+    text::Font,
+    texture::{Image, Texture2D},
+    RaylibHandle, RaylibThread,
+};
+
+use crate::tileset::Tileset;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TextureKey {
+    Rover,
+    Enemy,
+    Block,
+    Box,
+    Ground,
+    Wall,
+    Exit,
+    DoorLocked,
+    DoorOpen,
+    Key,
+    Switch,
+    GateClosed,
+    GateOpen,
+    PressurePlate,
+    Water,
+    Lava,
+    Ice,
+    Conveyor,
+    Charger,
+    Gem,
+    Checkpoint,
+    Goal,
+}
+
+impl TextureKey {
+    /// Finds the texture of a tile by the name of its file in the `tiles` folder, like `door_open`
+    pub fn from_tile_name(name: &str) -> Option<TextureKey> {
+        TEXTURES
+            .iter()
+            .find(|(_, path, _)| {
+                path.strip_prefix("tiles/")
+                    .and_then(|file| file.strip_suffix(".png"))
+                    == Some(name)
+            })
+            .map(|(key, _, _)| *key)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FontKey {
+    Geist,
+}
+
+/// Every texture as its key, its path in an asset pack and the PNG file embedded into the binary
+const TEXTURES: [(TextureKey, &str, &[u8]); 22] = [
+    (
+        TextureKey::Rover,
+        "rover.png",
+        include_bytes!("../assets/rover.png"),
+    ),
+    (
+        TextureKey::Enemy,
+        "enemy.png",
+        include_bytes!("../assets/enemy.png"),
+    ),
+    (
+        TextureKey::Block,
+        "block.png",
+        include_bytes!("../assets/block.png"),
+    ),
+    (
+        TextureKey::Box,
+        "box.png",
+        include_bytes!("../assets/box.png"),
+    ),
+    (
+        TextureKey::Ground,
+        "tiles/ground.png",
+        include_bytes!("../assets/tiles/ground.png"),
+    ),
+    (
+        TextureKey::Wall,
+        "tiles/wall.png",
+        include_bytes!("../assets/tiles/wall.png"),
+    ),
+    (
+        TextureKey::Exit,
+        "tiles/exit.png",
+        include_bytes!("../assets/tiles/exit.png"),
+    ),
+    (
+        TextureKey::DoorLocked,
+        "tiles/door.png",
+        include_bytes!("../assets/tiles/door.png"),
+    ),
+    (
+        TextureKey::DoorOpen,
+        "tiles/door_open.png",
+        include_bytes!("../assets/tiles/door_open.png"),
+    ),
+    (
+        TextureKey::Key,
+        "tiles/key.png",
+        include_bytes!("../assets/tiles/key.png"),
+    ),
+    (
+        TextureKey::Switch,
+        "tiles/switch.png",
+        include_bytes!("../assets/tiles/switch.png"),
+    ),
+    (
+        TextureKey::GateClosed,
+        "tiles/gate.png",
+        include_bytes!("../assets/tiles/gate.png"),
+    ),
+    (
+        TextureKey::GateOpen,
+        "tiles/gate_open.png",
+        include_bytes!("../assets/tiles/gate_open.png"),
+    ),
+    (
+        TextureKey::PressurePlate,
+        "tiles/plate.png",
+        include_bytes!("../assets/tiles/plate.png"),
+    ),
+    (
+        TextureKey::Water,
+        "tiles/water.png",
+        include_bytes!("../assets/tiles/water.png"),
+    ),
+    (
+        TextureKey::Lava,
+        "tiles/lava.png",
+        include_bytes!("../assets/tiles/lava.png"),
+    ),
+    (
+        TextureKey::Ice,
+        "tiles/ice.png",
+        include_bytes!("../assets/tiles/ice.png"),
+    ),
+    (
+        TextureKey::Conveyor,
+        "tiles/conveyor.png",
+        include_bytes!("../assets/tiles/conveyor.png"),
+    ),
+    (
+        TextureKey::Charger,
+        "tiles/charger.png",
+        include_bytes!("../assets/tiles/charger.png"),
+    ),
+    (
+        TextureKey::Gem,
+        "tiles/gem.png",
+        include_bytes!("../assets/tiles/gem.png"),
+    ),
+    (
+        TextureKey::Checkpoint,
+        "tiles/checkpoint.png",
+        include_bytes!("../assets/tiles/checkpoint.png"),
+    ),
+    (
+        TextureKey::Goal,
+        "tiles/goal.png",
+        include_bytes!("../assets/tiles/goal.png"),
+    ),
+];
+
+/// The folder with the tilesets in asset packs
+const TILESETS_DIRECTORY: &str = "tilesets";
+
+/// Every tileset that comes with the game as its name, its file and the sheet it names
+const TILESETS: [(&str, &str, &[u8]); 2] = [
+    (
+        "desert",
+        include_str!("../assets/tilesets/desert.tileset"),
+        include_bytes!("../assets/tilesets/desert.png"),
+    ),
+    (
+        "factory",
+        include_str!("../assets/tilesets/factory.tileset"),
+        include_bytes!("../assets/tilesets/factory.png"),
+    ),
+];
+
+const FONTS: [(FontKey, &str, &[u8]); 1] = [(
+    FontKey::Geist,
+    "fonts/GeistMono-Regular.ttf",
+    include_bytes!("../fonts/GeistMono-Regular.ttf"),
+)];
+
+/// Reads a file from the asset pack if it has one at this path and returns the embedded file
+/// otherwise, so asset packs only need to contain the files they change
+///
+/// * `asset_directory`: The directory of the asset pack, laid out like the `assets` folder
+fn asset_bytes(
+    asset_directory: Option<&Path>,
+    path: &str,
+    embedded: &'static [u8],
+) -> Result<Cow<'static, [u8]>, String> {
+    match asset_directory.map(|directory| directory.join(path)) {
+        Some(file) if file.is_file() => fs::read(&file)
+            .map(Cow::Owned)
+            .map_err(|error| format!("Error loading {}: {}", file.display(), error)),
+        _ => Ok(Cow::Borrowed(embedded)),
+    }
+}
+
+fn load_texture(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    asset_directory: Option<&Path>,
+    path: &str,
+    embedded: &'static [u8],
+) -> Result<Texture2D, String> {
+    let bytes = asset_bytes(asset_directory, path, embedded)?;
+    let image = Image::load_image_from_mem(".png", &bytes)?;
+
+    rl.load_texture_from_image(thread, &image)
+}
+
+/// Loads the embedded tilesets and the tilesets of the asset pack, which replace embedded
+/// tilesets with the same name. A tileset that can not be loaded is left out, so a broken file in
+/// an asset pack does not keep the game from starting
+fn load_tilesets(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    asset_directory: Option<&Path>,
+) -> HashMap<String, Tileset> {
+    let mut tilesets = HashMap::new();
+
+    for (name, source, sheet) in TILESETS {
+        match Tileset::from_source(rl, thread, source, |_| Ok(sheet.to_vec())) {
+            Ok(tileset) => {
+                tilesets.insert(name.to_string(), tileset);
+            }
+            Err(error) => eprintln!("Error loading the tileset {}: {}", name, error),
+        }
+    }
+
+    let directory = asset_directory.map(|directory| directory.join(TILESETS_DIRECTORY));
+    if let Some(Ok(entries)) = directory.map(fs::read_dir) {
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "tileset")
+            })
+            .collect();
+        files.sort();
+
+        for file in files {
+            let name = file.file_stem().unwrap_or_default().to_string_lossy();
+
+            match Tileset::load(rl, thread, &file) {
+                Ok(tileset) => {
+                    tilesets.insert(name.into_owned(), tileset);
+                }
+                Err(error) => eprintln!("Error loading {}: {}", file.display(), error),
+            }
+        }
+    }
+
+    tilesets
+}
+
+/// The textures and fonts everything is drawn with
+pub struct Assets {
+    textures: HashMap<TextureKey, Texture2D>,
+    fonts: HashMap<FontKey, Font>,
+    tilesets: HashMap<String, Tileset>,
+    /// Drawn in place of textures that could not be loaded, so they stand out without crashing
+    /// the game
+    fallback: Texture2D,
+}
+
+impl Assets {
+    /// Loads the embedded assets, replaced by the files of an asset pack where it has them
+    ///
+    /// * `asset_directory`: The directory of the asset pack, laid out like the `assets` folder
+    pub fn load(
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        asset_directory: Option<&Path>,
+    ) -> Result<Assets, String> {
+        let fallback = rl.load_texture_from_image(
+            thread,
+            &Image::gen_image_checked(32, 32, 4, 4, Color::MAGENTA, Color::BLACK),
+        )?;
+
+        // A texture of the asset pack that can not be loaded is replaced by the embedded one,
+        // and a texture that can not be loaded at all is left out, raylib logs why
+        let mut textures = HashMap::with_capacity(TEXTURES.len());
+        for (key, path, embedded) in TEXTURES {
+            let texture = load_texture(rl, thread, asset_directory, path, embedded)
+                .or_else(|_| load_texture(rl, thread, None, path, embedded));
+
+            if let Ok(texture) = texture {
+                textures.insert(key, texture);
+            }
+        }
+
+        let mut fonts = HashMap::with_capacity(FONTS.len());
+        for (key, path, embedded) in FONTS {
+            let bytes = asset_bytes(asset_directory, path, embedded)?;
+            let font = rl
+                .load_font_from_memory(thread, ".ttf", &bytes, 128, None)
+                .map_err(|error| format!("Error loading {}: {}", path, error))?;
+
+            fonts.insert(key, font);
+        }
+
+        let tilesets = load_tilesets(rl, thread, asset_directory);
+
+        Ok(Assets {
+            textures,
+            fonts,
+            tilesets,
+            fallback,
+        })
+    }
+
+    pub fn texture(&self, key: TextureKey) -> &Texture2D {
+        self.textures.get(&key).unwrap_or(&self.fallback)
+    }
+
+    pub fn font(&self, key: FontKey) -> &Font {
+        // Every font is loaded or the assets fail to load
+        &self.fonts[&key]
+    }
+
+    pub fn tileset(&self, name: &str) -> Option<&Tileset> {
+        self.tilesets.get(name)
+    }
+}
@@ -0,0 +1,154 @@
+use std::fmt;
+
+/// What the game was started with, on the command line
+#[derive(Default)]
+pub struct Options {
+    /// A level file to play right away instead of opening the menu
+    pub level: Option<String>,
+    pub script: Option<String>,
+    /// A directory with textures and fonts that replace the embedded ones
+    pub assets: Option<String>,
+    /// A directory of scripts to run on the level and export a heatmap of instead of playing
+    pub heatmap: Option<String>,
+    /// Where the heatmap is saved
+    pub output: Option<String>,
+    /// A GIF file or a directory of PNG files to save the playback of the level to instead of
+    /// playing
+    pub record: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum CliError {
+    MissingValue(String),
+    UnknownArgument(String),
+    /// A heatmap or a recording was asked for without a level, with the flag that asked for it
+    MissingLevel(String),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::MissingValue(flag) => write!(f, "{} expects a path", flag),
+            CliError::UnknownArgument(argument) => write!(
+                f,
+                "unknown argument {}, usage: rust-navigator [--level <file>] [--script <file>] \
+                 [--assets <directory>] [--heatmap <directory> [--output <file>]] [--record <file>]",
+                argument
+            ),
+            CliError::MissingLevel(flag) => write!(f, "{} needs a --level", flag),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+impl Options {
+    /// Parses `[--level <file>] [--script <file>] [--assets <directory>] [--heatmap <directory>
+    /// [--output <file>]] [--record <file>]`, without the name of the executable
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Options, CliError> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+
+        while let Some(argument) = args.next() {
+            let value = match argument.as_str() {
+                "--level" | "--script" | "--assets" | "--heatmap" | "--output" | "--record" => args
+                    .next()
+                    .ok_or_else(|| CliError::MissingValue(argument.clone()))?,
+                _ => return Err(CliError::UnknownArgument(argument)),
+            };
+
+            match argument.as_str() {
+                "--level" => options.level = Some(value),
+                "--script" => options.script = Some(value),
+                "--assets" => options.assets = Some(value),
+                "--heatmap" => options.heatmap = Some(value),
+                "--output" => options.output = Some(value),
+                _ => options.record = Some(value),
+            }
+        }
+
+        if options.level.is_none() {
+            if options.heatmap.is_some() {
+                return Err(CliError::MissingLevel("--heatmap".to_string()));
+            }
+
+            if options.record.is_some() {
+                return Err(CliError::MissingLevel("--record".to_string()));
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, CliError> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn no_arguments() {
+        let options = parse(&[]).unwrap();
+
+        assert!(options.level.is_none());
+        assert!(options.script.is_none());
+        assert!(options.heatmap.is_none());
+        assert!(options.record.is_none());
+    }
+
+    #[test]
+    fn every_flag() {
+        let options = parse(&[
+            "--level",
+            "maze.level",
+            "--script",
+            "lua/test.lua",
+            "--assets",
+            "pack",
+            "--heatmap",
+            "solutions",
+            "--output",
+            "map.png",
+            "--record",
+            "run.gif",
+        ])
+        .unwrap();
+
+        assert_eq!(options.level.as_deref(), Some("maze.level"));
+        assert_eq!(options.script.as_deref(), Some("lua/test.lua"));
+        assert_eq!(options.assets.as_deref(), Some("pack"));
+        assert_eq!(options.heatmap.as_deref(), Some("solutions"));
+        assert_eq!(options.output.as_deref(), Some("map.png"));
+        assert_eq!(options.record.as_deref(), Some("run.gif"));
+    }
+
+    #[test]
+    fn later_flags_win() {
+        let options = parse(&["--script", "a.lua", "--script", "b.lua"]).unwrap();
+
+        assert_eq!(options.script.as_deref(), Some("b.lua"));
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(
+            parse(&["--level"]),
+            Err(CliError::MissingValue(flag)) if flag == "--level"
+        ));
+        assert!(matches!(
+            parse(&["maze.level"]),
+            Err(CliError::UnknownArgument(argument)) if argument == "maze.level"
+        ));
+        assert!(matches!(
+            parse(&["--heatmap", "solutions"]),
+            Err(CliError::MissingLevel(flag)) if flag == "--heatmap"
+        ));
+        assert!(matches!(
+            parse(&["--record", "run.gif"]),
+            Err(CliError::MissingLevel(flag)) if flag == "--record"
+        ));
+    }
+}
@@ -1,6 +1,9 @@
 use raylib::prelude::*;
 
-use crate::rendering::Drawable;
+use crate::{
+    assets::{Assets, FontKey},
+    rendering::Drawable,
+};
 
 #[derive(Default)]
 pub struct Button {
@@ -53,13 +56,7 @@ impl Button {
 
 impl Drawable for Button {
     /// * `_position`: This parameter is ignored since a button's position is static
-    fn draw(
-        &self,
-        _position: (i32, i32),
-        d: &mut RaylibDrawHandle,
-        _textures: &std::collections::HashMap<String, Texture2D>,
-        fonts: &std::collections::HashMap<String, Font>,
-    ) {
+    fn draw(&self, _position: (i32, i32), d: &mut RaylibDrawHandle, assets: &Assets) {
         let hovered = self.is_hovered(d);
         let (text_color, bg_color) = match hovered {
             false => (self.color, self.bg_color),
@@ -68,7 +65,7 @@ impl Drawable for Button {
 
         d.draw_rectangle_rec(self.rect, bg_color);
         d.draw_text_ex(
-            fonts.get("geist").unwrap(),
+            assets.font(FontKey::Geist),
             &self.text,
             self.text_position,
             self.font_size,
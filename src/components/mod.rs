@@ -1 +1,3 @@
 pub mod button;
+pub mod stars;
+pub mod trail;
@@ -0,0 +1,62 @@
+use std::f32::consts::PI;
+
+use raylib::prelude::*;
+
+use crate::{assets::Assets, rendering::Drawable, scoring::MAX_STARS};
+
+/// A row of `MAX_STARS` stars of which the first `count` are filled in
+pub struct Stars {
+    pub count: u8,
+    pub radius: f32,
+}
+
+impl Stars {
+    pub fn new(count: u8, radius: f32) -> Self {
+        Self { count, radius }
+    }
+
+    pub fn width(&self) -> f32 {
+        MAX_STARS as f32 * self.radius * 2.5 - self.radius * 0.5
+    }
+
+    fn draw_star(&self, center: Vector2, color: Color, d: &mut RaylibDrawHandle) {
+        let mut points = vec![center];
+
+        // Triangle fans have to be wound counter-clockwise
+        for i in 0..=10 {
+            let angle = -PI / 2.0 - i as f32 * PI / 5.0;
+            let radius = match i % 2 {
+                0 => self.radius,
+                _ => self.radius * 0.45,
+            };
+
+            points.push(Vector2::new(
+                center.x + angle.cos() * radius,
+                center.y + angle.sin() * radius,
+            ));
+        }
+
+        d.draw_triangle_fan(&points, color);
+    }
+}
+
+impl Drawable for Stars {
+    /// * `position`: The top left corner of the row
+    fn draw(&self, position: (i32, i32), d: &mut RaylibDrawHandle, _assets: &Assets) {
+        for i in 0..MAX_STARS {
+            let center = Vector2::new(
+                position.0 as f32 + self.radius + i as f32 * self.radius * 2.5,
+                position.1 as f32 + self.radius,
+            );
+
+            self.draw_star(
+                center,
+                match i < self.count {
+                    true => Color::GOLD,
+                    false => Color::DARKGRAY,
+                },
+                d,
+            );
+        }
+    }
+}
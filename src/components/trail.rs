@@ -0,0 +1,124 @@
+use raylib::prelude::*;
+
+use crate::{
+    app::{tile_to_screen_pos, tile_to_screen_pos_centered},
+    assets::Assets,
+    rendering::Drawable,
+    robot::{Robot, RobotCommand, RobotResponse},
+};
+
+/// The color of the trail of each robot, repeated if there are more robots
+const COLORS: [Color; 4] = [Color::SKYBLUE, Color::GOLD, Color::LIME, Color::VIOLET];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Marker {
+    Turn,
+    /// Points in the direction that was scanned, in degrees
+    Scan(f32),
+}
+
+/// The path every robot took during playback, with where it turned and scanned
+pub struct Trail {
+    /// The tiles each robot has been on, in order
+    paths: Vec<Vec<(u8, u8)>>,
+    markers: Vec<(usize, (u8, u8), Marker)>,
+    /// Where a robot ran into something, crashed or was caught
+    collision: Option<(u8, u8)>,
+}
+
+impl Trail {
+    pub fn new(robots: &[Robot]) -> Self {
+        Self {
+            paths: robots
+                .iter()
+                .map(|robot| vec![(robot.x, robot.y)])
+                .collect(),
+            markers: vec![],
+            collision: None,
+        }
+    }
+
+    /// Adds what a command of a robot did to the trail
+    ///
+    /// * `robot`: The robot after the command ran
+    pub fn record(
+        &mut self,
+        index: usize,
+        command: RobotCommand,
+        robot: &Robot,
+        response: &RobotResponse,
+    ) {
+        let position = (robot.x, robot.y);
+
+        if self.paths[index].last() != Some(&position) {
+            self.paths[index].push(position);
+        }
+
+        match command {
+            RobotCommand::Left | RobotCommand::Right => {
+                self.markers.push((index, position, Marker::Turn))
+            }
+            RobotCommand::Scan => {
+                self.markers
+                    .push((index, position, Marker::Scan(robot.get_draw_rotation())))
+            }
+            _ => {}
+        }
+
+        if let RobotResponse::Error(error) = response {
+            self.collision = error.position();
+        }
+    }
+}
+
+impl Drawable for Trail {
+    /// * `_position`: This parameter is ignored since the trail is drawn where the robots were
+    fn draw(&self, _position: (i32, i32), d: &mut RaylibDrawHandle, _assets: &Assets) {
+        for (index, path) in self.paths.iter().enumerate() {
+            let color = COLORS[index % COLORS.len()].alpha(0.6);
+
+            for segment in path.windows(2) {
+                let start = tile_to_screen_pos_centered(segment[0].0, segment[0].1);
+                let end = tile_to_screen_pos_centered(segment[1].0, segment[1].1);
+
+                d.draw_line_ex(
+                    Vector2::new(start.0 as f32, start.1 as f32),
+                    Vector2::new(end.0 as f32, end.1 as f32),
+                    4.0,
+                    color,
+                );
+            }
+        }
+
+        for (index, position, marker) in &self.markers {
+            let color = COLORS[index % COLORS.len()];
+            let center = tile_to_screen_pos_centered(position.0, position.1);
+
+            match marker {
+                Marker::Turn => d.draw_circle(center.0, center.1, 4.0, color),
+                Marker::Scan(rotation) => {
+                    let angle = rotation.to_radians();
+                    d.draw_circle_lines(center.0, center.1, 8.0, color);
+                    d.draw_line_ex(
+                        Vector2::new(center.0 as f32, center.1 as f32),
+                        Vector2::new(
+                            center.0 as f32 + angle.cos() * 12.0,
+                            center.1 as f32 + angle.sin() * 12.0,
+                        ),
+                        2.0,
+                        color,
+                    );
+                }
+            }
+        }
+
+        if let Some((x, y)) = self.collision {
+            let screen_pos = tile_to_screen_pos(x, y);
+            d.draw_rectangle_lines_ex(
+                Rectangle::new(screen_pos.0 as f32, screen_pos.1 as f32, 32.0, 32.0),
+                3.0,
+                Color::RED,
+            );
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use std::{collections::HashMap, fs, io, path::Path};
+
+use raylib::color::Color;
+
+/// How many levels of red, green and blue the palette has, which makes 216 colors
+const LEVELS: u8 = 6;
+/// The largest code LZW compression uses in GIF files, after which it starts over
+const MAX_CODE: u16 = 4096;
+
+/// The entry of the palette closest to a color. Every frame uses the same palette, which is
+/// exact for the plain colors most of the game is drawn with
+fn palette_index(color: &Color) -> u8 {
+    let level = |value: u8| ((value as u16 * (LEVELS as u16 - 1) + 127) / 255) as u8;
+
+    level(color.r) * LEVELS * LEVELS + level(color.g) * LEVELS + level(color.b)
+}
+
+fn palette() -> Vec<u8> {
+    let mut palette = Vec::with_capacity(256 * 3);
+
+    for index in 0..=255u8 {
+        match index < LEVELS * LEVELS * LEVELS {
+            true => {
+                let level = |value: u8| (value as u16 * 255 / (LEVELS as u16 - 1)) as u8;
+                palette.push(level(index / (LEVELS * LEVELS)));
+                palette.push(level(index / LEVELS % LEVELS));
+                palette.push(level(index % LEVELS));
+            }
+            false => palette.extend([0, 0, 0]),
+        }
+    }
+
+    palette
+}
+
+/// Writes codes of a growing number of bits, starting with the lowest bit
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u8) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += size;
+
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+
+        self.bytes
+    }
+}
+
+/// Compresses the palette indices of a frame with the LZW variant of GIF files
+fn compress(indices: &[u8]) -> Vec<u8> {
+    let clear = 256;
+    let end = clear + 1;
+
+    let mut writer = BitWriter {
+        bytes: vec![],
+        buffer: 0,
+        bits: 0,
+    };
+    let mut codes: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next_code = end + 1;
+    let mut size = 9;
+
+    writer.write(clear, size);
+
+    let Some((first, rest)) = indices.split_first() else {
+        writer.write(end, size);
+        return writer.finish();
+    };
+
+    let mut prefix = *first as u16;
+
+    for index in rest {
+        if let Some(code) = codes.get(&(prefix, *index)) {
+            prefix = *code;
+            continue;
+        }
+
+        writer.write(prefix, size);
+
+        // The decoder adds its codes one step later, so the size grows once the next code no
+        // longer fits
+        if next_code >= 1 << size && size < 12 {
+            size += 1;
+        }
+
+        match next_code < MAX_CODE {
+            true => {
+                codes.insert((prefix, *index), next_code);
+                next_code += 1;
+            }
+            false => {
+                writer.write(clear, size);
+                codes.clear();
+                next_code = end + 1;
+                size = 9;
+            }
+        }
+
+        prefix = *index as u16;
+    }
+
+    writer.write(prefix, size);
+    writer.write(end, size);
+
+    writer.finish()
+}
+
+/// Writes the frames as an animated GIF that loops forever
+///
+/// * `frames`: The pixels of every frame, row by row
+/// * `delays`: How long each frame is shown, in hundredths of a second
+pub fn write_gif(
+    path: &Path,
+    width: u16,
+    height: u16,
+    frames: &[Vec<Color>],
+    delays: &[u16],
+) -> io::Result<()> {
+    let mut bytes = b"GIF89a".to_vec();
+
+    bytes.extend(width.to_le_bytes());
+    bytes.extend(height.to_le_bytes());
+    // A global palette of 256 colors
+    bytes.extend([0xf7, 0, 0]);
+    bytes.extend(palette());
+
+    // Loops the animation
+    bytes.extend([0x21, 0xff, 0x0b]);
+    bytes.extend(b"NETSCAPE2.0");
+    bytes.extend([0x03, 0x01, 0, 0, 0]);
+
+    for (frame, delay) in frames.iter().zip(delays) {
+        bytes.extend([0x21, 0xf9, 0x04, 0]);
+        bytes.extend(delay.to_le_bytes());
+        bytes.extend([0, 0]);
+
+        bytes.push(0x2c);
+        bytes.extend([0, 0, 0, 0]);
+        bytes.extend(width.to_le_bytes());
+        bytes.extend(height.to_le_bytes());
+        bytes.push(0);
+
+        let indices: Vec<u8> = frame.iter().map(palette_index).collect();
+
+        // The smallest code size, followed by the data in blocks of up to 255 bytes
+        bytes.push(8);
+        for block in compress(&indices).chunks(255) {
+            bytes.push(block.len() as u8);
+            bytes.extend(block);
+        }
+        bytes.push(0);
+    }
+
+    bytes.push(0x3b);
+
+    fs::write(path, bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decodes the codes written by `compress` like a GIF reader would. Returns the palette
+    /// indices and how often the table was full and started over
+    fn decompress(bytes: &[u8]) -> (Vec<u8>, usize) {
+        let (clear, end) = (256, 257);
+        let mut table: Vec<Vec<u8>> = (0..=255).map(|index| vec![index]).collect();
+        table.extend([vec![], vec![]]);
+
+        let mut indices = vec![];
+        let mut resets = 0;
+        let mut previous: Option<Vec<u8>> = None;
+        let mut size = 9;
+        let mut position = 0;
+
+        loop {
+            let code = (0..size).fold(0, |code, bit| {
+                let index = position + bit;
+                let value = (bytes[index / 8] >> (index % 8)) & 1;
+
+                code | (value as usize) << bit
+            });
+            position += size;
+
+            if code == clear {
+                resets += usize::from(position > size);
+                table.truncate(258);
+                previous = None;
+                size = 9;
+                continue;
+            }
+
+            if code == end {
+                return (indices, resets);
+            }
+
+            let entry = match (table.get(code), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) if code == table.len() => {
+                    [previous.as_slice(), &previous[..1]].concat()
+                }
+                _ => panic!("unknown code {}", code),
+            };
+
+            if let Some(previous) = previous {
+                if table.len() < MAX_CODE as usize {
+                    table.push([previous.as_slice(), &entry[..1]].concat());
+                }
+            }
+
+            if table.len() == 1 << size && size < 12 {
+                size += 1;
+            }
+
+            indices.extend(&entry);
+            previous = Some(entry);
+        }
+    }
+
+    /// Palette indices that repeat rarely enough to fill the table, from a linear congruential
+    /// generator
+    fn noise(length: usize, colors: u32) -> Vec<u8> {
+        let mut state: u32 = 1;
+
+        (0..length)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                ((state >> 16) % colors) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn empty_frame() {
+        assert_eq!(decompress(&compress(&[])), (vec![], 0));
+    }
+
+    #[test]
+    fn single_color_frame() {
+        let indices = vec![palette_index(&Color::BLACK); 352 * 352];
+
+        assert_eq!(decompress(&compress(&indices)).0, indices);
+    }
+
+    #[test]
+    fn codes_below_the_first_boundary() {
+        let indices = noise(200, 216);
+
+        assert_eq!(decompress(&compress(&indices)), (indices, 0));
+    }
+
+    #[test]
+    fn codes_up_to_each_boundary() {
+        // Almost every index adds a code when there are many colors, so these lengths end right
+        // around each size change
+        for boundary in [512, 1024, 2048, 4096] {
+            for length in boundary - 260..boundary - 256 {
+                let indices = noise(length, 216);
+                let (decoded, _) = decompress(&compress(&indices));
+
+                assert_eq!(decoded, indices, "{} indices", length);
+            }
+        }
+    }
+
+    #[test]
+    fn full_table_starts_over() {
+        for colors in [2, 16, 216] {
+            let indices = noise(100_000, colors);
+            let (decoded, resets) = decompress(&compress(&indices));
+
+            assert_eq!(decoded, indices, "{} colors", colors);
+            assert!(resets > 0, "{} colors", colors);
+        }
+    }
+}
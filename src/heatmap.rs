@@ -0,0 +1,248 @@
+use std::{fs, path::Path};
+
+use raylib::prelude::*;
+
+use crate::{
+    app::tile_to_screen_pos,
+    assets::Assets,
+    interface::instructions::{simulate, Simulation},
+    level::Level,
+    paths::numbered_path,
+    rendering::{export_png, Canvas, Drawable},
+    robot::{RobotCommand, RobotResponse},
+    team::{Team, Trace},
+};
+
+/// How often the robots of many runs of a variant were on each tile and where they failed
+pub struct Heatmap {
+    width: u8,
+    visits: Vec<u32>,
+    failures: Vec<u32>,
+}
+
+impl Heatmap {
+    pub fn new(width: u8, height: u8) -> Self {
+        let size = width as usize * height as usize;
+
+        Self {
+            width,
+            visits: vec![0; size],
+            failures: vec![0; size],
+        }
+    }
+
+    fn index(&self, position: (u8, u8)) -> usize {
+        position.1 as usize * self.width as usize + position.0 as usize
+    }
+
+    /// Plays a trace back like the game does, counting every tile a robot moved onto and the
+    /// tile it failed at
+    pub fn add_run(&mut self, level: &Level, variant_index: usize, trace: &Trace) {
+        let variant = &level.variants[variant_index];
+        let mut world = variant.world.clone();
+        let mut team = Team::new(
+            variant.robots.clone(),
+            level.objectives.clone(),
+            &variant.world,
+        );
+
+        for robot in &team.robots {
+            let index = self.index((robot.x, robot.y));
+            self.visits[index] += 1;
+        }
+
+        for (robot_index, command) in trace {
+            if matches!(command, RobotCommand::End) {
+                break;
+            }
+
+            let before = team.robots[*robot_index];
+            let response = team.execute(*robot_index, *command, &mut world);
+            let robot = team.robots[*robot_index];
+
+            if (robot.x, robot.y) != (before.x, before.y) {
+                let index = self.index((robot.x, robot.y));
+                self.visits[index] += 1;
+            }
+
+            if let RobotResponse::Error(error) = response {
+                let index = self.index(error.position().unwrap_or((robot.x, robot.y)));
+                self.failures[index] += 1;
+
+                break;
+            }
+
+            if team.is_won() {
+                break;
+            }
+        }
+    }
+}
+
+impl Drawable for Heatmap {
+    /// * `_position`: This parameter is ignored since the heatmap covers the world
+    fn draw(&self, _position: (i32, i32), d: &mut RaylibDrawHandle, _assets: &Assets) {
+        let most_visits = self.visits.iter().copied().max().unwrap_or(0).max(1);
+
+        for (index, (visits, failures)) in self.visits.iter().zip(&self.failures).enumerate() {
+            let x = (index % self.width as usize) as u8;
+            let y = (index / self.width as usize) as u8;
+            let screen_pos = tile_to_screen_pos(x, y);
+
+            // Rarely visited tiles are a faint yellow, the most visited ones a strong red
+            if *visits > 0 {
+                let heat = *visits as f32 / most_visits as f32;
+                let color = Color::new(255, (220.0 * (1.0 - heat)) as u8, 0, 255);
+
+                d.draw_rectangle(
+                    screen_pos.0,
+                    screen_pos.1,
+                    32,
+                    32,
+                    color.alpha(0.25 + heat * 0.45),
+                );
+            }
+
+            if *failures > 0 {
+                let text = failures.to_string();
+                let text_width = d.measure_text(&text, 16);
+
+                d.draw_circle(screen_pos.0 + 16, screen_pos.1 + 16, 11.0, Color::MAROON);
+                d.draw_text(
+                    &text,
+                    screen_pos.0 + 16 - text_width / 2,
+                    screen_pos.1 + 8,
+                    16,
+                    Color::WHITE,
+                );
+            }
+        }
+    }
+}
+
+/// Runs every Lua script in a directory on a level and collects a heatmap for each variant.
+/// Scripts that can not be read or loaded are skipped
+///
+/// Returns the heatmaps, how many scripts ran and why each skipped script was skipped
+pub fn collect_heatmaps(
+    level: &Level,
+    directory: &str,
+) -> Result<(Vec<Heatmap>, usize, Vec<String>), String> {
+    let mut files: Vec<_> = fs::read_dir(directory)
+        .map_err(|error| format!("Error reading {}: {}", directory, error))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "lua"))
+        .collect();
+    files.sort();
+
+    let mut heatmaps: Vec<Heatmap> = level
+        .variants
+        .iter()
+        .map(|variant| Heatmap::new(variant.world.width, variant.world.height))
+        .collect();
+
+    let mut runs = 0;
+    let mut skipped = vec![];
+
+    for file in &files {
+        let script = match fs::read(file) {
+            Ok(script) => script,
+            Err(error) => {
+                skipped.push(format!("{}: {}", file.display(), error));
+                continue;
+            }
+        };
+
+        let simulations: Vec<Simulation> = level
+            .variants
+            .iter()
+            .map(|variant| {
+                simulate(
+                    &variant.robots,
+                    variant.world.clone(),
+                    level.objectives.clone(),
+                    std::slice::from_ref(&script),
+                )
+            })
+            .collect();
+
+        // A script that does not load has empty traces, which would only dilute the heatmap
+        if let Some(error) = simulations.iter().find_map(|run| run.load_error.as_ref()) {
+            skipped.push(format!("{}: {}", file.display(), error));
+            continue;
+        }
+
+        for (variant_index, simulation) in simulations.iter().enumerate() {
+            heatmaps[variant_index].add_run(level, variant_index, &simulation.trace);
+        }
+        runs += 1;
+    }
+
+    Ok((heatmaps, runs, skipped))
+}
+
+/// Draws a variant with its heatmap into a render texture and saves it as a PNG file
+pub fn export_heatmap(
+    d: &mut RaylibDrawHandle,
+    assets: &Assets,
+    level: &Level,
+    variant_index: usize,
+    heatmap: &Heatmap,
+    path: &Path,
+) -> Result<(), String> {
+    let world = &level.variants[variant_index].world;
+    let size = tile_to_screen_pos(world.width, world.height);
+    let tileset = level
+        .tileset
+        .as_deref()
+        .and_then(|name| assets.tileset(name));
+
+    let image = Canvas::new(size.0, size.1)?.draw(d, |d| {
+        d.clear_background(Color::BLACK);
+        world.draw_themed(d, assets, tileset);
+        heatmap.draw((0, 0), d, assets);
+    })?;
+
+    export_png(&image, path)
+}
+
+/// Runs the scripts in a directory on a level and saves the heatmap of every variant. Levels
+/// with more than one variant get a file for each, numbered like `heatmap-2.png`
+pub fn run_heatmap(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    assets: &Assets,
+    level: &Level,
+    directory: &str,
+    output: &str,
+) -> Result<(), String> {
+    let (heatmaps, runs, skipped) = collect_heatmaps(level, directory)?;
+    let output = Path::new(output);
+    let mut d = rl.begin_drawing(thread);
+
+    for (variant_index, heatmap) in heatmaps.iter().enumerate() {
+        let path = match heatmaps.len() {
+            1 => output.to_path_buf(),
+            _ => numbered_path(output, variant_index + 1),
+        };
+
+        export_heatmap(&mut d, assets, level, variant_index, heatmap, &path)?;
+
+        match skipped.len() {
+            0 => println!("Saved the heatmap of {} runs to {}", runs, path.display()),
+            count => println!(
+                "Saved the heatmap of {} runs to {}, skipped {} scripts",
+                runs,
+                path.display(),
+                count
+            ),
+        }
+    }
+
+    for reason in &skipped {
+        eprintln!("Skipped {}", reason);
+    }
+
+    Ok(())
+}
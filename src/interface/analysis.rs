@@ -0,0 +1,833 @@
+use std::fmt;
+
+/// Code-quality metrics of a script, not counting the main function the script returns
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Metrics {
+    pub statements: usize,
+    pub functions: usize,
+    pub loops: usize,
+    pub conditionals: usize,
+    pub max_depth: usize,
+}
+
+#[derive(Debug)]
+pub struct AnalysisError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AnalysisError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AnalysisError {}
+
+type Result<T> = std::result::Result<T, AnalysisError>;
+
+pub fn analyze(source: &[u8]) -> Result<Metrics> {
+    let source = String::from_utf8_lossy(source);
+    let tokens = tokenize(&source)?;
+
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        depth: 0,
+        metrics: Metrics::default(),
+    };
+    parser.chunk()?;
+
+    Ok(parser.metrics)
+}
+
+/// Counts the lines with code on them, leaving out blank lines and comments. Strings that span
+/// several lines count on each of them
+pub fn count_code_lines(source: &[u8]) -> Result<usize> {
+    let source = String::from_utf8_lossy(source);
+    let mut lines: Vec<usize> = tokenize(&source)?
+        .into_iter()
+        .filter(|(token, _, _)| *token != Token::Eof)
+        .flat_map(|(_, start, end)| start..=end)
+        .collect();
+    lines.dedup();
+
+    Ok(lines.len())
+}
+
+const KEYWORDS: [&str; 22] = [
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in",
+    "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+// Longer symbols have to come first so they are matched before their prefixes
+const SYMBOLS: [&str; 33] = [
+    "...", "..", "==", "~=", "<=", ">=", "<<", ">>", "//", "::", "+", "-", "*", "/", "%", "^", "#",
+    "&", "~", "|", "<", ">", "=", "(", ")", "{", "}", "[", "]", ";", ":", ",", ".",
+];
+
+const BINARY_OPERATORS: [&str; 21] = [
+    "+", "-", "*", "/", "//", "%", "^", "..", "==", "~=", "<", "<=", ">", ">=", "and", "or", "&",
+    "|", "~", "<<", ">>",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Name(String),
+    Keyword(&'static str),
+    Symbol(&'static str),
+    Number,
+    String,
+    Eof,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Name(name) => write!(f, "'{}'", name),
+            Token::Keyword(keyword) => write!(f, "'{}'", keyword),
+            Token::Symbol(symbol) => write!(f, "'{}'", symbol),
+            Token::Number => write!(f, "number"),
+            Token::String => write!(f, "string"),
+            Token::Eof => write!(f, "end of file"),
+        }
+    }
+}
+
+/// Splits a script into tokens, with the lines each token starts and ends on
+fn tokenize(source: &str) -> Result<Vec<(Token, usize, usize)>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = vec![];
+    let mut line = 1;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c == '\n' {
+            line += 1;
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c == '-' && chars.get(i + 1) == Some(&'-') {
+            i += 2;
+
+            match long_bracket_level(&chars, i) {
+                Some(level) => i = skip_long_bracket(&chars, i, level, &mut line)?,
+                None => {
+                    while i < chars.len() && chars[i] != '\n' {
+                        i += 1;
+                    }
+                }
+            }
+        } else if c == '[' && long_bracket_level(&chars, i).is_some() {
+            let level = long_bracket_level(&chars, i).unwrap();
+            let start = line;
+            i = skip_long_bracket(&chars, i, level, &mut line)?;
+            tokens.push((Token::String, start, line));
+        } else if c == '"' || c == '\'' {
+            let start = line;
+            i += 1;
+
+            loop {
+                match chars.get(i) {
+                    Some('\\') => {
+                        if chars.get(i + 1) == Some(&'\n') {
+                            line += 1;
+                        }
+                        i += 2;
+                    }
+                    Some(&quote) if quote == c => {
+                        i += 1;
+                        tokens.push((Token::String, start, line));
+                        break;
+                    }
+                    Some('\n') | None => {
+                        return Err(AnalysisError {
+                            line,
+                            message: "unfinished string".to_string(),
+                        })
+                    }
+                    Some(_) => i += 1,
+                }
+            }
+        } else if c.is_ascii_digit()
+            || (c == '.' && chars.get(i + 1).is_some_and(char::is_ascii_digit))
+        {
+            tokens.push((Token::Number, line, line));
+
+            while i < chars.len() {
+                let is_exponent = matches!(chars[i], 'e' | 'E' | 'p' | 'P')
+                    && matches!(chars.get(i + 1), Some('+') | Some('-'));
+
+                if is_exponent {
+                    i += 2;
+                } else if chars[i].is_ascii_alphanumeric() || chars[i] == '.' || chars[i] == '_' {
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+
+            let word: String = chars[start..i].iter().collect();
+            tokens.push((
+                match KEYWORDS.iter().find(|keyword| **keyword == word) {
+                    Some(keyword) => Token::Keyword(keyword),
+                    None => Token::Name(word),
+                },
+                line,
+                line,
+            ));
+        } else {
+            let symbol = SYMBOLS.iter().find(|symbol| {
+                symbol
+                    .chars()
+                    .enumerate()
+                    .all(|(offset, c)| chars.get(i + offset) == Some(&c))
+            });
+
+            match symbol {
+                Some(symbol) => {
+                    tokens.push((Token::Symbol(symbol), line, line));
+                    i += symbol.len();
+                }
+                None => {
+                    return Err(AnalysisError {
+                        line,
+                        message: format!("unexpected character '{}'", c),
+                    })
+                }
+            }
+        }
+    }
+
+    tokens.push((Token::Eof, line, line));
+
+    Ok(tokens)
+}
+
+/// Returns the level of the long bracket (`[[`, `[=[`, ...) starting at `i`
+fn long_bracket_level(chars: &[char], i: usize) -> Option<usize> {
+    if chars.get(i) != Some(&'[') {
+        return None;
+    }
+
+    let mut level = 0;
+    while chars.get(i + 1 + level) == Some(&'=') {
+        level += 1;
+    }
+
+    match chars.get(i + 1 + level) {
+        Some('[') => Some(level),
+        _ => None,
+    }
+}
+
+/// Returns the index right after the closing long bracket
+fn skip_long_bracket(chars: &[char], i: usize, level: usize, line: &mut usize) -> Result<usize> {
+    let start_line = *line;
+    let mut i = i + level + 2;
+
+    while i < chars.len() {
+        if chars[i] == '\n' {
+            *line += 1;
+        }
+
+        if chars[i] == ']'
+            && (1..=level).all(|offset| chars.get(i + offset) == Some(&'='))
+            && chars.get(i + level + 1) == Some(&']')
+        {
+            return Ok(i + level + 2);
+        }
+
+        i += 1;
+    }
+
+    Err(AnalysisError {
+        line: start_line,
+        message: "unfinished long string or comment".to_string(),
+    })
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize, usize)>,
+    position: usize,
+    depth: usize,
+    metrics: Metrics,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position].0
+    }
+
+    fn peek_next(&self) -> &Token {
+        let index = (self.position + 1).min(self.tokens.len() - 1);
+        &self.tokens[index].0
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.position].0.clone();
+        if self.position < self.tokens.len() - 1 {
+            self.position += 1;
+        }
+
+        token
+    }
+
+    fn check_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Token::Keyword(k) if *k == keyword)
+    }
+
+    fn check_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Token::Symbol(s) if *s == symbol)
+    }
+
+    fn accept_keyword(&mut self, keyword: &str) -> bool {
+        let matched = self.check_keyword(keyword);
+        if matched {
+            self.advance();
+        }
+
+        matched
+    }
+
+    fn accept_symbol(&mut self, symbol: &str) -> bool {
+        let matched = self.check_symbol(symbol);
+        if matched {
+            self.advance();
+        }
+
+        matched
+    }
+
+    fn error<T>(&self, expected: &str) -> Result<T> {
+        let (token, line, _) = &self.tokens[self.position];
+
+        Err(AnalysisError {
+            line: *line,
+            message: format!("expected {} near {}", expected, token),
+        })
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+        match self.accept_keyword(keyword) {
+            true => Ok(()),
+            false => self.error(&format!("'{}'", keyword)),
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<()> {
+        match self.accept_symbol(symbol) {
+            true => Ok(()),
+            false => self.error(&format!("'{}'", symbol)),
+        }
+    }
+
+    fn expect_name(&mut self) -> Result<()> {
+        match self.peek() {
+            Token::Name(_) => {
+                self.advance();
+                Ok(())
+            }
+            _ => self.error("name"),
+        }
+    }
+
+    /// The script has to return its main function, which is not counted as a user-defined
+    /// function and does not add to the nesting depth
+    fn chunk(&mut self) -> Result<()> {
+        while !self.block_ends() {
+            if self.check_keyword("return") {
+                match self.peek_next() {
+                    Token::Keyword("function") => {
+                        self.advance();
+                        self.advance();
+                        self.function_body()?;
+                        self.accept_symbol(";");
+                    }
+                    _ => self.block()?,
+                }
+
+                break;
+            }
+
+            self.statement()?;
+        }
+
+        match self.peek() {
+            Token::Eof => Ok(()),
+            _ => self.error("end of file"),
+        }
+    }
+
+    fn block_ends(&self) -> bool {
+        matches!(
+            self.peek(),
+            Token::Eof
+                | Token::Keyword("end")
+                | Token::Keyword("else")
+                | Token::Keyword("elseif")
+                | Token::Keyword("until")
+        )
+    }
+
+    fn block(&mut self) -> Result<()> {
+        while !self.block_ends() {
+            if self.accept_keyword("return") {
+                self.metrics.statements += 1;
+
+                if !self.block_ends() && !self.check_symbol(";") {
+                    self.expression_list()?;
+                }
+                self.accept_symbol(";");
+
+                break;
+            }
+
+            self.statement()?;
+        }
+
+        Ok(())
+    }
+
+    fn nested_block(&mut self) -> Result<()> {
+        self.depth += 1;
+        self.metrics.max_depth = self.metrics.max_depth.max(self.depth);
+
+        let result = self.block();
+        self.depth -= 1;
+
+        result
+    }
+
+    fn statement(&mut self) -> Result<()> {
+        if self.accept_symbol(";") {
+            return Ok(());
+        }
+
+        self.metrics.statements += 1;
+
+        match self.advance() {
+            Token::Symbol("::") => {
+                self.expect_name()?;
+                self.expect_symbol("::")
+            }
+            Token::Keyword("break") => Ok(()),
+            Token::Keyword("goto") => self.expect_name(),
+            Token::Keyword("do") => {
+                self.nested_block()?;
+                self.expect_keyword("end")
+            }
+            Token::Keyword("while") => {
+                self.metrics.loops += 1;
+                self.expression()?;
+                self.expect_keyword("do")?;
+                self.nested_block()?;
+                self.expect_keyword("end")
+            }
+            Token::Keyword("repeat") => {
+                self.metrics.loops += 1;
+                self.nested_block()?;
+                self.expect_keyword("until")?;
+                self.expression()
+            }
+            Token::Keyword("if") => {
+                self.metrics.conditionals += 1;
+                self.expression()?;
+                self.expect_keyword("then")?;
+                self.nested_block()?;
+
+                while self.accept_keyword("elseif") {
+                    self.expression()?;
+                    self.expect_keyword("then")?;
+                    self.nested_block()?;
+                }
+
+                if self.accept_keyword("else") {
+                    self.nested_block()?;
+                }
+
+                self.expect_keyword("end")
+            }
+            Token::Keyword("for") => {
+                self.metrics.loops += 1;
+                self.expect_name()?;
+
+                if self.accept_symbol("=") {
+                    self.expression_list()?;
+                } else {
+                    while self.accept_symbol(",") {
+                        self.expect_name()?;
+                    }
+                    self.expect_keyword("in")?;
+                    self.expression_list()?;
+                }
+
+                self.expect_keyword("do")?;
+                self.nested_block()?;
+                self.expect_keyword("end")
+            }
+            Token::Keyword("function") => {
+                self.metrics.functions += 1;
+                self.expect_name()?;
+                while self.accept_symbol(".") {
+                    self.expect_name()?;
+                }
+                if self.accept_symbol(":") {
+                    self.expect_name()?;
+                }
+
+                self.nested_function_body()
+            }
+            Token::Keyword("local") => {
+                if self.accept_keyword("function") {
+                    self.metrics.functions += 1;
+                    self.expect_name()?;
+
+                    return self.nested_function_body();
+                }
+
+                loop {
+                    self.expect_name()?;
+
+                    // Attributes like <const> and <close>
+                    if self.accept_symbol("<") {
+                        self.expect_name()?;
+                        self.expect_symbol(">")?;
+                    }
+
+                    if !self.accept_symbol(",") {
+                        break;
+                    }
+                }
+
+                match self.accept_symbol("=") {
+                    true => self.expression_list(),
+                    false => Ok(()),
+                }
+            }
+            _ => {
+                // Either an assignment or a function call, both start with a suffixed expression
+                self.position -= 1;
+                self.suffixed_expression()?;
+
+                if self.check_symbol("=") || self.check_symbol(",") {
+                    while self.accept_symbol(",") {
+                        self.suffixed_expression()?;
+                    }
+                    self.expect_symbol("=")?;
+                    self.expression_list()?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    fn nested_function_body(&mut self) -> Result<()> {
+        self.depth += 1;
+        self.metrics.max_depth = self.metrics.max_depth.max(self.depth);
+
+        let result = self.function_body();
+        self.depth -= 1;
+
+        result
+    }
+
+    fn function_body(&mut self) -> Result<()> {
+        self.expect_symbol("(")?;
+
+        if !self.check_symbol(")") {
+            loop {
+                if self.accept_symbol("...") {
+                    break;
+                }
+
+                self.expect_name()?;
+
+                if !self.accept_symbol(",") {
+                    break;
+                }
+            }
+        }
+
+        self.expect_symbol(")")?;
+        self.block()?;
+        self.expect_keyword("end")
+    }
+
+    fn expression_list(&mut self) -> Result<()> {
+        self.expression()?;
+        while self.accept_symbol(",") {
+            self.expression()?;
+        }
+
+        Ok(())
+    }
+
+    fn expression(&mut self) -> Result<()> {
+        loop {
+            while matches!(
+                self.peek(),
+                Token::Keyword("not")
+                    | Token::Symbol("-")
+                    | Token::Symbol("#")
+                    | Token::Symbol("~")
+            ) {
+                self.advance();
+            }
+
+            self.simple_expression()?;
+
+            let is_binary_operator = match self.peek() {
+                Token::Keyword(operator) | Token::Symbol(operator) => {
+                    BINARY_OPERATORS.contains(operator)
+                }
+                _ => false,
+            };
+
+            if !is_binary_operator {
+                return Ok(());
+            }
+
+            self.advance();
+        }
+    }
+
+    fn simple_expression(&mut self) -> Result<()> {
+        match self.peek() {
+            Token::Number
+            | Token::String
+            | Token::Keyword("nil")
+            | Token::Keyword("true")
+            | Token::Keyword("false")
+            | Token::Symbol("...") => {
+                self.advance();
+                Ok(())
+            }
+            Token::Keyword("function") => {
+                self.advance();
+                self.metrics.functions += 1;
+                self.nested_function_body()
+            }
+            Token::Symbol("{") => self.table_constructor(),
+            _ => self.suffixed_expression(),
+        }
+    }
+
+    fn suffixed_expression(&mut self) -> Result<()> {
+        match self.peek() {
+            Token::Name(_) => {
+                self.advance();
+            }
+            Token::Symbol("(") => {
+                self.advance();
+                self.expression()?;
+                self.expect_symbol(")")?;
+            }
+            _ => return self.error("expression"),
+        }
+
+        loop {
+            match self.peek() {
+                Token::Symbol(".") => {
+                    self.advance();
+                    self.expect_name()?;
+                }
+                Token::Symbol("[") => {
+                    self.advance();
+                    self.expression()?;
+                    self.expect_symbol("]")?;
+                }
+                Token::Symbol(":") => {
+                    self.advance();
+                    self.expect_name()?;
+                    self.call_arguments()?;
+                }
+                Token::Symbol("(") | Token::Symbol("{") | Token::String => self.call_arguments()?,
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn call_arguments(&mut self) -> Result<()> {
+        match self.peek() {
+            Token::String => {
+                self.advance();
+                Ok(())
+            }
+            Token::Symbol("{") => self.table_constructor(),
+            Token::Symbol("(") => {
+                self.advance();
+                if !self.check_symbol(")") {
+                    self.expression_list()?;
+                }
+                self.expect_symbol(")")
+            }
+            _ => self.error("function arguments"),
+        }
+    }
+
+    fn table_constructor(&mut self) -> Result<()> {
+        self.expect_symbol("{")?;
+
+        while !self.check_symbol("}") {
+            if self.accept_symbol("[") {
+                self.expression()?;
+                self.expect_symbol("]")?;
+                self.expect_symbol("=")?;
+            } else if matches!(self.peek(), Token::Name(_))
+                && matches!(self.peek_next(), Token::Symbol("="))
+            {
+                self.advance();
+                self.advance();
+            }
+
+            self.expression()?;
+
+            if !self.accept_symbol(",") && !self.accept_symbol(";") {
+                break;
+            }
+        }
+
+        self.expect_symbol("}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(source: &str) -> (usize, String) {
+        let error = analyze(source.as_bytes()).unwrap_err();
+
+        (error.line, error.message)
+    }
+
+    #[test]
+    fn main_function_is_not_counted() {
+        let metrics = analyze(b"return function(robot)\n    forward()\nend\n").unwrap();
+
+        assert_eq!(metrics.statements, 1);
+        assert_eq!(metrics.functions, 0);
+        assert_eq!(metrics.max_depth, 0);
+    }
+
+    #[test]
+    fn nested_loops_and_functions() {
+        let source = "
+local function turn_around()
+    left()
+    left()
+end
+
+return function(robot)
+    for i = 1, 4 do
+        while not scan() do
+            if is_marked() then
+                turn_around()
+            end
+        end
+    end
+
+    local step = function() forward() end
+    repeat step() until robot.scan()
+end
+";
+        let metrics = analyze(source.as_bytes()).unwrap();
+
+        assert_eq!(metrics.statements, 11);
+        assert_eq!(metrics.functions, 2);
+        assert_eq!(metrics.loops, 3);
+        assert_eq!(metrics.conditionals, 1);
+        assert_eq!(metrics.max_depth, 3);
+    }
+
+    #[test]
+    fn long_strings_and_comments() {
+        let source = r#"
+--[==[ a comment
+with ]] inside ]==]
+local text = [[
+end ( return
+]] .. 'it\'s' .. "say \"do\""
+-- if then
+return function(robot)
+    print(text) --[[ while ]] forward()
+end
+"#;
+        let metrics = analyze(source.as_bytes()).unwrap();
+
+        assert_eq!(metrics.statements, 3);
+        assert_eq!(metrics.loops, 0);
+        assert_eq!(metrics.conditionals, 0);
+    }
+
+    #[test]
+    fn code_lines() {
+        let source = "x = 1 --[[ a comment
+that spans lines ]]
+
+local text = [[
+two lines]]
+-- return
+return function(robot) end
+";
+
+        assert_eq!(count_code_lines(source.as_bytes()).unwrap(), 4);
+    }
+
+    #[test]
+    fn lines_after_long_strings() {
+        assert_eq!(
+            error("local a = [[\n\n]]\nlocal = 1\n"),
+            (4, "expected name near '='".to_string())
+        );
+    }
+
+    #[test]
+    fn unfinished_strings_and_comments() {
+        assert_eq!(
+            error("local a = 1\n--[[ comment\n\n"),
+            (2, "unfinished long string or comment".to_string())
+        );
+        assert_eq!(
+            error("local a = [=[\n]]\n"),
+            (1, "unfinished long string or comment".to_string())
+        );
+        assert_eq!(
+            error("print('text)\nforward()\n"),
+            (1, "unfinished string".to_string())
+        );
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert_eq!(
+            error("x =\n"),
+            (2, "expected expression near end of file".to_string())
+        );
+        assert_eq!(
+            error("if x then\n    forward()\n"),
+            (3, "expected 'end' near end of file".to_string())
+        );
+        assert_eq!(
+            error("return function(robot)\n    forward(\nend\n"),
+            (3, "expected expression near 'end'".to_string())
+        );
+        assert_eq!(
+            error("while true\n    forward()\nend\n"),
+            (2, "expected 'do' near 'forward'".to_string())
+        );
+        assert_eq!(
+            error("x = 1 @ 2\n"),
+            (1, "unexpected character '@'".to_string())
+        );
+    }
+}
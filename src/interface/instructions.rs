@@ -1,159 +1,487 @@
 use std::{
-    sync::{mpsc::channel, Arc, Mutex},
+    cell::Cell,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
     thread,
+    time::Duration,
 };
 
-use mlua::{Function, Lua};
+use mlua::{Function, HookTriggers, Lua, LuaOptions, MultiValue, StdLib, Table, Value};
 
 use crate::{
-    robot::{Robot, RobotCommand, RobotResponse},
+    level::Objective,
+    robot::{Robot, RobotCommand, RobotError, RobotResponse},
+    team::{Team, Trace},
     world::World,
 };
 
-pub fn simulate(
+const MAX_COMMANDS: usize = 2048;
+/// How many Lua instructions a script may run between two commands before it is stopped
+const MAX_INSTRUCTIONS: u32 = 10_000_000;
+/// How many bytes a script may print in total before it is stopped
+const MAX_OUTPUT_BYTES: usize = 64 * 1024;
+/// How many bytes the Lua state of a script may use, which also limits how long a single call
+/// of a library function like `string.rep` can take
+const MAX_MEMORY: usize = 64 * 1024 * 1024;
+/// How many instructions pass between two checks of the instruction count
+const HOOK_INTERVAL: u32 = 1000;
+/// How long the simulation waits for a script that neither sends a command nor runs Lua code,
+/// like one that waits for input
+const SCRIPT_TIMEOUT: Duration = Duration::from_secs(5);
+
+const COMMANDS: [(&str, RobotCommand); 9] = [
+    ("forward", RobotCommand::Forward),
+    ("left", RobotCommand::Left),
+    ("right", RobotCommand::Right),
+    ("scan", RobotCommand::Scan),
+    ("pick_up", RobotCommand::PickUp),
+    ("use", RobotCommand::Use),
+    ("mark", RobotCommand::Mark),
+    ("unmark", RobotCommand::Unmark),
+    ("is_marked", RobotCommand::IsMarked),
+];
+
+/// What a script sends to the simulation. Output goes through the same channel as the commands,
+/// so it arrives in the order it was written in
+enum ScriptMessage {
+    Command(RobotCommand),
+    Output(String),
+    /// The script stopped with an error, nothing is sent after this
+    Error(String),
+    /// The script failed before its main function ran, nothing is sent after this
+    LoadError(String),
+}
+
+/// Why a script was stopped. Once it is set every hook and protected call raises it again, so
+/// the script can not catch it and keep running
+type Stop = Rc<Cell<Option<&'static str>>>;
+
+/// What running the scripts of a variant produced
+#[derive(Clone)]
+pub struct Simulation {
+    pub trace: Trace,
+    /// The lines the scripts printed, with the number of commands that ran before each of them
+    pub output: Vec<(usize, String)>,
+    /// Why the first script that failed on its own stopped, like calling a function that does
+    /// not exist. Robots that crash or get caught are not script errors
+    pub error: Option<String>,
+    /// Why the first script that did not start failed, like an error at the top level or not
+    /// returning a main function
+    pub load_error: Option<String>,
+}
+
+/// Creates a Lua function that sends a command to the simulation and waits for the response
+///
+/// * `instructions`: How many instructions ran since the last command, reset by every command
+fn create_command_function(
     lua: &Lua,
-    mut robot: Robot,
-    world: World,
-    file: Vec<u8>,
-) -> Result<Vec<RobotCommand>, Box<dyn std::error::Error>> {
-    let globals = lua.globals();
+    command: RobotCommand,
+    tx_in: Sender<ScriptMessage>,
+    rx_out: Arc<Mutex<Receiver<RobotResponse>>>,
+    instructions: Rc<Cell<u32>>,
+    stop: Stop,
+) -> mlua::Result<Function<'_>> {
+    // The arguments are ignored so the functions can be called with `robot:forward()` as well
+    lua.create_function(move |_, _: MultiValue| {
+        instructions.set(0);
+        let _ = tx_in.send(ScriptMessage::Command(command));
+
+        let response = rx_out.lock().unwrap().recv();
+        if matches!(
+            response,
+            Ok(RobotResponse::Error(_)) | Ok(RobotResponse::Finished) | Err(_)
+        ) {
+            stop.set(stop.get().or(Some("Robot stopped")));
+        }
 
-    globals.set(
-        "print",
-        mlua::Value::Function(lua.create_function(|_, _v: String| Ok(())).unwrap()),
-    )?;
+        match response {
+            Ok(RobotResponse::Ok) => Ok(None),
+            Ok(RobotResponse::Scan(state))
+            | Ok(RobotResponse::Action(state))
+            | Ok(RobotResponse::Marked(state)) => Ok(Some(state)),
+            Ok(RobotResponse::Error(error)) => Err(mlua::Error::RuntimeError(error.to_string())),
+            // Stops the script once the robot reached an exit or the simulation ended
+            Ok(RobotResponse::Finished) | Err(_) => {
+                Err(mlua::Error::RuntimeError("Robot stopped".to_string()))
+            }
+        }
+    })
+}
+
+/// Creates a Lua function that sends its arguments to the simulation as output, joined by
+/// `separator` and followed by `end`
+///
+/// * `written`: How many bytes the script printed so far, shared by every output function
+fn create_output_function<'lua>(
+    lua: &'lua Lua,
+    separator: &'static str,
+    end: &'static str,
+    tx_in: Sender<ScriptMessage>,
+    written: Rc<Cell<usize>>,
+) -> mlua::Result<Function<'lua>> {
+    lua.create_function(move |_, values: MultiValue| {
+        let text = values
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<mlua::Result<Vec<String>>>()?
+            .join(separator)
+            + end;
+
+        // Output costs no commands, so a script printing in a loop is stopped here instead
+        written.set(written.get() + text.len());
+        if written.get() > MAX_OUTPUT_BYTES {
+            return Err(mlua::Error::RuntimeError(
+                "Printed too much output".to_string(),
+            ));
+        }
+
+        let _ = tx_in.send(ScriptMessage::Output(text));
 
-    let (tx_in, rx_in) = channel::<RobotCommand>();
-    let (tx_out, rx_out_raw) = channel::<RobotResponse>();
+        Ok(())
+    })
+}
 
-    let rx_out = Arc::new(Mutex::new(rx_out_raw));
+/// Wraps a function that catches errors, like `pcall`, so it raises the error again once the
+/// script was stopped
+fn create_protected_function<'lua>(
+    lua: &'lua Lua,
+    function: Function<'lua>,
+    stop: Stop,
+) -> mlua::Result<Function<'lua>> {
+    let key = lua.create_registry_value(function)?;
+
+    lua.create_function(move |lua, arguments: MultiValue| {
+        let results: MultiValue = lua.registry_value::<Function>(&key)?.call(arguments)?;
+
+        match stop.get() {
+            Some(reason) => Err(mlua::Error::RuntimeError(reason.to_string())),
+            None => Ok(results),
+        }
+    })
+}
 
-    let tx_in_forward = tx_in.clone();
-    let rx_out_forward = rx_out.clone();
+/// Creates the Lua state of a script. Replays run the scripts of other players, so only the
+/// libraries that can not reach files, other programs or the game itself are loaded
+fn create_lua() -> mlua::Result<Lua> {
+    let lua = Lua::new_with(
+        StdLib::COROUTINE | StdLib::TABLE | StdLib::STRING | StdLib::UTF8 | StdLib::MATH,
+        LuaOptions::default(),
+    )?;
+    lua.set_memory_limit(MAX_MEMORY)?;
 
-    let tx_in_left = tx_in.clone();
-    let rx_out_left = rx_out.clone();
+    // The base library can still run other files
+    lua.globals().set("dofile", Value::Nil)?;
+    lua.globals().set("loadfile", Value::Nil)?;
 
-    let tx_in_right = tx_in.clone();
-    let rx_out_right = rx_out.clone();
+    Ok(lua)
+}
 
-    let tx_in_scan = tx_in.clone();
-    let rx_out_scan = rx_out.clone();
+/// Loads the script of one robot and returns its main function with the handle it receives, a
+/// table with the `id` of the robot and the same functions as the globals
+///
+/// * `cancelled`: Set once the simulation no longer waits for the script, which stops it
+fn load_script(
+    lua: &Lua,
+    id: usize,
+    script: Vec<u8>,
+    tx_in: Sender<ScriptMessage>,
+    rx_out: Receiver<RobotResponse>,
+    cancelled: Arc<AtomicBool>,
+) -> mlua::Result<(Function<'_>, Table<'_>)> {
+    let globals = lua.globals();
 
+    let written = Rc::new(Cell::new(0));
     globals.set(
-        "forward",
-        mlua::Value::Function(lua.create_function(move |_, _: ()| {
-            let _ = tx_in_forward.send(RobotCommand::Forward);
-
-            match rx_out_forward.lock().unwrap().recv() {
-                Ok(response) => match response {
-                    RobotResponse::Ok => Ok(()),
-                    _ => Err(mlua::Error::RuntimeError("Forward error".to_string())),
-                },
-                Err(_) => Err(mlua::Error::RuntimeError("Forward error".to_string())),
+        "print",
+        create_output_function(lua, "\t", "\n", tx_in.clone(), written.clone())?,
+    )?;
+    let io = lua.create_table()?;
+    io.set(
+        "write",
+        create_output_function(lua, "", "", tx_in.clone(), written)?,
+    )?;
+    globals.set("io", io)?;
+
+    // A script that loops without sending commands would keep the simulation waiting forever,
+    // and a script the simulation stopped waiting for would keep its thread running
+    let stop: Stop = Rc::new(Cell::new(None));
+    let instructions = Rc::new(Cell::new(0));
+    let counter = instructions.clone();
+    let hook_stop = stop.clone();
+    lua.set_hook(
+        HookTriggers::new().every_nth_instruction(HOOK_INTERVAL),
+        move |_, _| {
+            counter.set(counter.get() + HOOK_INTERVAL);
+
+            if counter.get() > MAX_INSTRUCTIONS {
+                hook_stop.set(hook_stop.get().or(Some("Ran too long without a command")));
+            }
+
+            if cancelled.load(Ordering::Relaxed) {
+                hook_stop.set(hook_stop.get().or(Some("The simulation ended")));
             }
-        })?),
+
+            match hook_stop.get() {
+                Some(reason) => Err(mlua::Error::RuntimeError(reason.to_string())),
+                None => Ok(()),
+            }
+        },
+    );
+
+    for name in ["pcall", "xpcall"] {
+        let function = create_protected_function(lua, globals.get(name)?, stop.clone())?;
+        globals.set(name, function)?;
+    }
+
+    let coroutine: Table = globals.get("coroutine")?;
+    coroutine.set(
+        "resume",
+        create_protected_function(lua, coroutine.get("resume")?, stop.clone())?,
     )?;
 
-    globals.set(
-        "left",
-        mlua::Value::Function(lua.create_function(move |_, _: ()| {
-            let _ = tx_in_left.send(RobotCommand::Left);
+    let rx_out = Arc::new(Mutex::new(rx_out));
+    let handle = lua.create_table()?;
+    handle.set("id", id)?;
+
+    for (name, command) in COMMANDS {
+        let function = create_command_function(
+            lua,
+            command,
+            tx_in.clone(),
+            rx_out.clone(),
+            instructions.clone(),
+            stop.clone(),
+        )?;
+
+        globals.set(name, function.clone())?;
+        handle.set(name, function)?;
+    }
+
+    match lua.load(script).set_name("=script").eval()? {
+        Value::Function(main_function) => Ok((main_function, handle)),
+        _ => Err(mlua::Error::RuntimeError(
+            "The script has to return its main function".to_string(),
+        )),
+    }
+}
 
-            // This cannot fail
-            let _response = rx_out_left.lock().unwrap().recv();
+/// Runs the script of one robot and returns the message that ends it
+fn run_script(
+    id: usize,
+    script: Vec<u8>,
+    tx_in: Sender<ScriptMessage>,
+    rx_out: Receiver<RobotResponse>,
+    cancelled: Arc<AtomicBool>,
+) -> ScriptMessage {
+    let lua = match create_lua() {
+        Ok(lua) => lua,
+        Err(error) => return ScriptMessage::LoadError(error_message(&error)),
+    };
 
-            Ok(())
-        })?),
-    )?;
+    let message = match load_script(&lua, id, script, tx_in, rx_out, cancelled) {
+        Ok((main_function, handle)) => match main_function.call::<_, ()>(handle) {
+            Ok(()) => ScriptMessage::Command(RobotCommand::End),
+            Err(error) => ScriptMessage::Error(error_message(&error)),
+        },
+        Err(error) => ScriptMessage::LoadError(error_message(&error)),
+    };
 
-    globals.set(
-        "right",
-        mlua::Value::Function(lua.create_function(move |_, _: ()| {
-            let _ = tx_in_right.send(RobotCommand::Right);
+    message
+}
 
-            // This cannot fail
-            let _response = rx_out_right.lock().unwrap().recv();
+/// Checks that a script compiles, without running it
+pub fn check_script(script: &[u8]) -> mlua::Result<()> {
+    Lua::new().load(script).into_function()?;
 
-            Ok(())
-        })?),
-    )?;
+    Ok(())
+}
 
-    globals.set(
-        "scan",
-        mlua::Value::Function(lua.create_function(move |_, _: ()| {
-            let _ = tx_in_scan.send(RobotCommand::Scan);
+/// The message of an error of a script without the stack traceback, like
+/// `script:3: attempt to call a nil value (global 'jump')`
+fn error_message(error: &mlua::Error) -> String {
+    match error {
+        mlua::Error::CallbackError { cause, .. } => error_message(cause),
+        mlua::Error::RuntimeError(message) => {
+            message.lines().next().unwrap_or_default().to_string()
+        }
+        error => error.to_string(),
+    }
+}
 
-            match rx_out_scan.lock().unwrap().recv().unwrap() {
-                RobotResponse::Scan(state) => Ok(state),
-                _ => Err(mlua::Error::RuntimeError("Scan error".to_string())),
-            }
-        })?),
-    )?;
+/// Prefixes a line with the id of the robot that wrote it if there is more than one robot
+fn label_line(line: &str, robot_count: usize, index: usize) -> String {
+    match robot_count > 1 {
+        true => format!("{}: {}", index + 1, line),
+        false => line.to_string(),
+    }
+}
 
-    let in_handle = thread::spawn(move || {
-        let mut commands: Vec<RobotCommand> = vec![];
-        const MAX_COMMANDS: usize = 2048;
+/// Runs the scripts of every robot of a variant. The robots take turns running one command each,
+/// so the trace and the output are the same no matter how the threads of the scripts are
+/// scheduled
+///
+/// * `scripts`: The script of each robot, robots without a script of their own run the last one
+pub fn simulate(
+    robots: &[Robot],
+    mut world: World,
+    objectives: Vec<Objective>,
+    scripts: &[Vec<u8>],
+) -> Simulation {
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let mut channels: Vec<Option<(Receiver<ScriptMessage>, Sender<RobotResponse>)>> = robots
+        .iter()
+        .enumerate()
+        .map(|(index, _)| {
+            let script = scripts[index.min(scripts.len() - 1)].clone();
+            let (tx_in, rx_in) = channel::<ScriptMessage>();
+            let (tx_out, rx_out) = channel::<RobotResponse>();
+
+            // The thread is not joined, a script that is stuck waiting would block the game
+            // otherwise. It stops at the next hook once the simulation is cancelled
+            let cancelled = cancelled.clone();
+            thread::spawn(move || {
+                let message = run_script(index + 1, script, tx_in.clone(), rx_out, cancelled);
+                let _ = tx_in.send(message);
+            });
+
+            Some((rx_in, tx_out))
+        })
+        .collect();
+
+    let mut team = Team::new(robots.to_vec(), objectives, &world);
+    let mut trace: Trace = vec![];
+    let mut output = vec![];
+    let mut error = None;
+    let mut load_error = None;
+    // Output of every robot that does not end with a new line yet
+    let mut partial_lines = vec![String::new(); robots.len()];
+
+    // Robots drop out of the turns once they finished or their script ended
+    'turns: while channels.iter().any(Option::is_some) {
+        for (index, channel) in channels.iter_mut().enumerate() {
+            let Some((rx_in, tx_out)) = channel else {
+                continue;
+            };
 
-        loop {
-            if commands.len() >= MAX_COMMANDS {
-                tx_out.send(RobotResponse::Error).unwrap();
-                break;
-            }
+            // Output written before the next command belongs to this turn
+            let command = loop {
+                match rx_in.recv_timeout(SCRIPT_TIMEOUT) {
+                    Ok(ScriptMessage::Output(text)) => {
+                        let partial = &mut partial_lines[index];
+                        *partial += &text;
 
-            let response = match rx_in.recv() {
-                Ok(command) => {
-                    commands.push(command);
-
-                    match command {
-                        RobotCommand::Forward => match robot.forward(&world) {
-                            Ok(_) => RobotResponse::Ok,
-                            Err(_) => RobotResponse::Error,
-                        },
-                        RobotCommand::Left => {
-                            robot.left();
-                            RobotResponse::Ok
-                        }
-                        RobotCommand::Right => {
-                            robot.right();
-                            RobotResponse::Ok
+                        while let Some(end) = partial.find('\n') {
+                            let line: String = partial.drain(..=end).collect();
+                            let line = label_line(&line[..end], robots.len(), index);
+
+                            output.push((trace.len(), line));
                         }
-                        RobotCommand::Scan => RobotResponse::Scan(robot.scan(&world)),
-                        RobotCommand::End => break,
                     }
+                    Ok(ScriptMessage::Command(command)) => break Some(command),
+                    Ok(ScriptMessage::Error(message)) => {
+                        error.get_or_insert(label_line(&message, robots.len(), index));
+                        break None;
+                    }
+                    Ok(ScriptMessage::LoadError(message)) => {
+                        load_error.get_or_insert(label_line(&message, robots.len(), index));
+                        break None;
+                    }
+                    Err(RecvTimeoutError::Timeout) => {
+                        error.get_or_insert(label_line("Stopped responding", robots.len(), index));
+                        break None;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => break None,
+                }
+            };
+
+            let command = match command {
+                Some(RobotCommand::End) | None => {
+                    *channel = None;
+                    continue;
                 }
-                Err(_) => break,
+                Some(command) => command,
             };
 
-            if robot.is_on_end_tile(&world) {
-                tx_out.send(RobotResponse::Error).unwrap();
+            if trace.len() >= MAX_COMMANDS {
+                let _ = tx_out.send(RobotResponse::Error(RobotError::TooManyCommands));
+                cancelled.store(true, Ordering::Relaxed);
 
-                break;
+                return Simulation {
+                    trace,
+                    output,
+                    error,
+                    load_error,
+                };
             }
 
-            let should_break = match response {
-                RobotResponse::Error => true,
-                _ => false,
-            };
+            trace.push((index, command));
+
+            let response = team.execute(index, command, &mut world);
+            let failed = matches!(response, RobotResponse::Error(_));
+            let finished = matches!(response, RobotResponse::Finished);
 
-            tx_out.send(response).unwrap();
+            let _ = tx_out.send(response);
+
+            // Losing any robot loses the variant, so every robot stops like in the playback
+            if failed {
+                break 'turns;
+            }
 
-            if should_break {
-                break;
+            if finished {
+                *channel = None;
             }
         }
+    }
 
-        commands
-    });
+    cancelled.store(true, Ordering::Relaxed);
 
-    let chunk = lua.load(file);
-    // TODO: Handle this error
-    let main_function: Function = chunk.eval().unwrap();
-    // TODO: Handle this error
-    match main_function.call::<_, ()>(()) {
-        Ok(_) => tx_in.send(RobotCommand::End).unwrap(),
-        Err(_) => (),
-    };
+    // Output without a new line at the end still counts as a line
+    for (index, line) in partial_lines.iter().enumerate() {
+        if !line.is_empty() {
+            output.push((trace.len(), label_line(line, robots.len(), index)));
+        }
+    }
+
+    Simulation {
+        trace,
+        output,
+        error,
+        load_error,
+    }
+}
 
-    Ok(in_handle.join().unwrap())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::default_level;
+
+    #[test]
+    fn scripts_can_not_catch_being_stopped() {
+        let level = default_level();
+        let variant = &level.variants[0];
+        let script = "return function(robot)
+    while true do
+        pcall(function()
+            while true do end
+        end)
+    end
+end
+";
+
+        let simulation = simulate(
+            &variant.robots,
+            variant.world.clone(),
+            vec![],
+            &[script.as_bytes().to_vec()],
+        );
+
+        assert_eq!(
+            simulation.error.as_deref(),
+            Some("Ran too long without a command")
+        );
+    }
 }
@@ -1,2 +1,2 @@
+pub mod analysis;
 pub mod instructions;
-
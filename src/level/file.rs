@@ -0,0 +1,682 @@
+use std::{fs, path::Path};
+
+use crate::{
+    robot::{Direction, Robot},
+    world::{
+        dynamic::{GateTimer, Mover, MoverKind},
+        Tile, World,
+    },
+};
+
+use super::{Level, LevelError, Objective, Par, Requirement, Variant};
+
+// A level file is a list of `<keyword> <values...>` lines. Every `variant` line is followed by
+// the rows of its tiles. Robots are placed with `<x> <y> <direction>` and an optional battery
+// capacity, the first one on the `variant` line and any further ones on `robot` lines. Enemies
+// and blocks are placed the same way without a battery, boxes with `box <x> <y>`. `gate_timer`
+// toggles the gates of the variant after the given number of ticks, `fog` hides the tiles of
+// every variant until a robot explored them and `tileset` draws the level with the sprites of a
+// built-in or asset pack tileset. `optimal` is the length of the shortest solution, which the
+// check of the editor fills in:
+//
+// id default
+// par 22 12 4
+// optimal 18
+// require min_loops 1
+// objective collect_gems
+// fog
+// tileset desert
+// variant 1 1 right 20
+// robot 1 2 right
+// enemy 3 3 up
+// block 2 3 left
+// box 2 2
+// gate_timer 4
+// ######
+// #..*o#
+// #...oE
+// #...o#
+// ######
+//
+// Besides ground (.), walls (#) and the exit (E) there are locked doors (D), open doors (d),
+// keys (k), switches (s), closed gates (G), open gates (g), pressure plates (p), water (~),
+// lava (L), ice (i), conveyors (<, >, ^, v), chargers (c), gems (*), checkpoints (1 to 9) and
+// goals for boxes (o)
+
+fn parse_error<T>(line: usize, message: impl ToString) -> Result<T, LevelError> {
+    Err(LevelError::Parse {
+        line,
+        message: message.to_string(),
+    })
+}
+
+fn parse_number(line: usize, value: Option<&str>) -> Result<usize, LevelError> {
+    match value.map(str::parse) {
+        Some(Ok(number)) => Ok(number),
+        _ => parse_error(line, "expected a number"),
+    }
+}
+
+fn direction_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Left => "left",
+        Direction::Right => "right",
+        Direction::Up => "up",
+        Direction::Down => "down",
+    }
+}
+
+fn parse_direction(line: usize, value: Option<&str>) -> Result<Direction, LevelError> {
+    match value {
+        Some("left") => Ok(Direction::Left),
+        Some("right") => Ok(Direction::Right),
+        Some("up") => Ok(Direction::Up),
+        Some("down") => Ok(Direction::Down),
+        _ => parse_error(line, "expected left, right, up or down"),
+    }
+}
+
+fn requirement_name(requirement: &Requirement) -> (&'static str, usize) {
+    match *requirement {
+        Requirement::MinFunctions(count) => ("min_functions", count),
+        Requirement::MinLoops(count) => ("min_loops", count),
+        Requirement::MinConditionals(count) => ("min_conditionals", count),
+        Requirement::MaxStatements(count) => ("max_statements", count),
+        Requirement::MaxDepth(depth) => ("max_depth", depth),
+    }
+}
+
+fn objective_name(objective: &Objective) -> &'static str {
+    match objective {
+        Objective::CollectGems => "collect_gems",
+        Objective::VisitCheckpoints => "visit_checkpoints",
+        Objective::FillGoals => "fill_goals",
+    }
+}
+
+/// Parses `<x> <y> <direction> [battery]`
+fn parse_robot<'a>(
+    line: usize,
+    mut words: impl Iterator<Item = &'a str>,
+) -> Result<Robot, LevelError> {
+    let x = parse_number(line, words.next())?;
+    let y = parse_number(line, words.next())?;
+    let facing = parse_direction(line, words.next())?;
+    let battery = match words.next() {
+        Some(value) => Some(parse_number(line, Some(value))?),
+        None => None,
+    };
+
+    if x > u8::MAX as usize || y > u8::MAX as usize {
+        return parse_error(line, "robot is outside of the world");
+    }
+
+    let robot = Robot::new(x as u8, y as u8, facing);
+    match battery {
+        Some(capacity) if capacity > u16::MAX as usize => parse_error(line, "battery is too large"),
+        Some(capacity) => Ok(robot.with_battery(capacity as u16)),
+        None => Ok(robot),
+    }
+}
+
+/// Parses `<x> <y> <direction>`
+fn parse_mover<'a>(
+    line: usize,
+    kind: MoverKind,
+    mut words: impl Iterator<Item = &'a str>,
+) -> Result<Mover, LevelError> {
+    let x = parse_number(line, words.next())?;
+    let y = parse_number(line, words.next())?;
+    let facing = parse_direction(line, words.next())?;
+
+    if x > u8::MAX as usize || y > u8::MAX as usize {
+        return parse_error(line, "mover is outside of the world");
+    }
+
+    Ok(Mover::new(x as u8, y as u8, kind, facing))
+}
+
+fn serialize_mover(mover: &Mover) -> String {
+    let name = match mover.kind {
+        MoverKind::Enemy => "enemy",
+        MoverKind::Block => "block",
+    };
+
+    format!(
+        "{} {} {} {}",
+        name,
+        mover.x,
+        mover.y,
+        direction_name(mover.facing)
+    )
+}
+
+fn serialize_robot(robot: &Robot) -> String {
+    let mut line = format!(
+        "{} {} {}",
+        robot.x,
+        robot.y,
+        direction_name(robot.get_facing())
+    );
+
+    if let Some(battery) = robot.battery {
+        line += &format!(" {}", battery.capacity);
+    }
+
+    line
+}
+
+struct VariantBuilder {
+    line: usize,
+    robots: Vec<Robot>,
+    movers: Vec<Mover>,
+    boxes: Vec<(u8, u8)>,
+    gate_timer: Option<GateTimer>,
+    rows: Vec<Vec<Tile>>,
+}
+
+impl VariantBuilder {
+    fn build(self) -> Result<Variant, LevelError> {
+        let height = self.rows.len();
+        let width = self.rows.first().map(Vec::len).unwrap_or(0);
+
+        if height == 0 || width == 0 {
+            return parse_error(self.line, "variant has no tiles");
+        }
+
+        if width > u8::MAX as usize || height > u8::MAX as usize {
+            return parse_error(self.line, "variant is too large");
+        }
+
+        if self.rows.iter().any(|row| row.len() != width) {
+            return parse_error(self.line, "rows of a variant have to be the same length");
+        }
+
+        for (i, robot) in self.robots.iter().enumerate() {
+            if robot.x as usize >= width || robot.y as usize >= height {
+                return parse_error(self.line, "robot is outside of the world");
+            }
+
+            if self.robots[..i]
+                .iter()
+                .any(|other| (other.x, other.y) == (robot.x, robot.y))
+            {
+                return parse_error(self.line, "robots have to start on different tiles");
+            }
+        }
+
+        let mut world = World::new((width as u8, height as u8));
+        for (y, row) in self.rows.iter().enumerate() {
+            for (x, tile) in row.iter().enumerate() {
+                if let Err(error) = world.set_tile((x as u8, y as u8), *tile) {
+                    return parse_error(self.line, error);
+                }
+            }
+        }
+
+        if !world.has_closed_border() {
+            return parse_error(self.line, "the border has to be walls or exits");
+        }
+
+        if !world.has_numbered_checkpoints() {
+            return parse_error(self.line, "checkpoints have to be numbered 1 to n");
+        }
+
+        if self
+            .robots
+            .iter()
+            .any(|robot| !world.get_tile((robot.x, robot.y)).can_start_on())
+        {
+            return parse_error(self.line, "robots can not start on this tile");
+        }
+
+        for mover in self.movers {
+            if world.mover_at((mover.x, mover.y)).is_some()
+                || self
+                    .robots
+                    .iter()
+                    .any(|robot| (robot.x, robot.y) == (mover.x, mover.y))
+            {
+                return parse_error(self.line, "movers have to start on free tiles");
+            }
+
+            if let Err(error) = world.add_mover(mover) {
+                return parse_error(self.line, error);
+            }
+        }
+
+        for position in self.boxes {
+            if world.has_box(position)
+                || world.mover_at(position).is_some()
+                || self
+                    .robots
+                    .iter()
+                    .any(|robot| (robot.x, robot.y) == position)
+            {
+                return parse_error(self.line, "boxes have to start on free tiles");
+            }
+
+            if let Err(error) = world.add_box(position) {
+                return parse_error(self.line, error);
+            }
+        }
+
+        world.gate_timer = self.gate_timer;
+
+        Ok(Variant {
+            world,
+            robots: self.robots,
+        })
+    }
+}
+
+impl Level {
+    pub fn load(path: impl AsRef<Path>) -> Result<Level, LevelError> {
+        let source = fs::read_to_string(path).map_err(LevelError::Io)?;
+
+        Level::parse(&source)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), LevelError> {
+        if let Some(directory) = path.as_ref().parent() {
+            fs::create_dir_all(directory).map_err(LevelError::Io)?;
+        }
+
+        fs::write(path, self.serialize()).map_err(LevelError::Io)
+    }
+
+    pub fn parse(source: &str) -> Result<Level, LevelError> {
+        let mut id = None;
+        let mut par = None;
+        let mut requirements = vec![];
+        let mut objectives = vec![];
+        let mut optimal_commands = None;
+        let mut fog = false;
+        let mut tileset = None;
+        let mut variants = vec![];
+        let mut builder: Option<VariantBuilder> = None;
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut words = line.split_whitespace();
+
+            match words.next() {
+                Some("id") => match words.next() {
+                    Some(value) => id = Some(value.to_string()),
+                    None => return parse_error(line_number, "expected an id"),
+                },
+                Some("par") => {
+                    par = Some(Par {
+                        max_commands: parse_number(line_number, words.next())?,
+                        max_lines: parse_number(line_number, words.next())?,
+                        max_scans: parse_number(line_number, words.next())?,
+                    })
+                }
+                Some("optimal") => {
+                    optimal_commands = Some(parse_number(line_number, words.next())?)
+                }
+                Some("require") => {
+                    let name = words.next();
+                    let value = parse_number(line_number, words.next())?;
+
+                    requirements.push(match name {
+                        Some("min_functions") => Requirement::MinFunctions(value),
+                        Some("min_loops") => Requirement::MinLoops(value),
+                        Some("min_conditionals") => Requirement::MinConditionals(value),
+                        Some("max_statements") => Requirement::MaxStatements(value),
+                        Some("max_depth") => Requirement::MaxDepth(value),
+                        _ => return parse_error(line_number, "unknown requirement"),
+                    });
+                }
+                Some("objective") => objectives.push(match words.next() {
+                    Some("collect_gems") => Objective::CollectGems,
+                    Some("visit_checkpoints") => Objective::VisitCheckpoints,
+                    Some("fill_goals") => Objective::FillGoals,
+                    _ => return parse_error(line_number, "unknown objective"),
+                }),
+                Some("fog") => fog = true,
+                Some("tileset") => match words.next() {
+                    Some(name) => tileset = Some(name.to_string()),
+                    None => return parse_error(line_number, "expected a tileset"),
+                },
+                Some("variant") => {
+                    if let Some(builder) = builder.take() {
+                        variants.push(builder.build()?);
+                    }
+
+                    builder = Some(VariantBuilder {
+                        line: line_number,
+                        robots: vec![parse_robot(line_number, words)?],
+                        movers: vec![],
+                        boxes: vec![],
+                        gate_timer: None,
+                        rows: vec![],
+                    });
+                }
+                Some("robot") => {
+                    let Some(builder) = builder.as_mut() else {
+                        return parse_error(line_number, "robot outside of a variant");
+                    };
+
+                    builder.robots.push(parse_robot(line_number, words)?);
+                }
+                Some(keyword @ ("enemy" | "block")) => {
+                    let Some(builder) = builder.as_mut() else {
+                        return parse_error(line_number, "mover outside of a variant");
+                    };
+
+                    let kind = match keyword {
+                        "enemy" => MoverKind::Enemy,
+                        _ => MoverKind::Block,
+                    };
+                    builder.movers.push(parse_mover(line_number, kind, words)?);
+                }
+                Some("box") => {
+                    let Some(builder) = builder.as_mut() else {
+                        return parse_error(line_number, "box outside of a variant");
+                    };
+
+                    let x = parse_number(line_number, words.next())?;
+                    let y = parse_number(line_number, words.next())?;
+                    if x > u8::MAX as usize || y > u8::MAX as usize {
+                        return parse_error(line_number, "box is outside of the world");
+                    }
+
+                    builder.boxes.push((x as u8, y as u8));
+                }
+                Some("gate_timer") => {
+                    let Some(builder) = builder.as_mut() else {
+                        return parse_error(line_number, "gate timer outside of a variant");
+                    };
+
+                    builder.gate_timer = match parse_number(line_number, words.next())? {
+                        period @ 1..=255 => Some(GateTimer::new(period as u8)),
+                        _ => return parse_error(line_number, "gate timer has to be 1 to 255"),
+                    };
+                }
+                _ => {
+                    let Some(builder) = builder.as_mut() else {
+                        return parse_error(line_number, "expected a keyword");
+                    };
+
+                    let row: Option<Vec<Tile>> = line.chars().map(Tile::from_char).collect();
+                    match row {
+                        Some(row) => builder.rows.push(row),
+                        None => return parse_error(line_number, "unknown tile"),
+                    }
+                }
+            }
+        }
+
+        if let Some(builder) = builder.take() {
+            variants.push(builder.build()?);
+        }
+
+        let Some(id) = id else {
+            return parse_error(1, "level has no id");
+        };
+
+        let Some(par) = par else {
+            return parse_error(1, "level has no par");
+        };
+
+        if variants.is_empty() {
+            return parse_error(1, "level has no variants");
+        }
+
+        Ok(Level {
+            id,
+            variants,
+            par,
+            optimal_commands,
+            requirements,
+            objectives,
+            fog,
+            tileset,
+        })
+    }
+
+    pub fn serialize(&self) -> String {
+        let mut lines = vec![
+            format!("id {}", self.id),
+            format!(
+                "par {} {} {}",
+                self.par.max_commands, self.par.max_lines, self.par.max_scans
+            ),
+        ];
+
+        if let Some(optimal_commands) = self.optimal_commands {
+            lines.push(format!("optimal {}", optimal_commands));
+        }
+
+        for requirement in &self.requirements {
+            let (name, value) = requirement_name(requirement);
+            lines.push(format!("require {} {}", name, value));
+        }
+
+        for objective in &self.objectives {
+            lines.push(format!("objective {}", objective_name(objective)));
+        }
+
+        if self.fog {
+            lines.push("fog".to_string());
+        }
+
+        if let Some(tileset) = &self.tileset {
+            lines.push(format!("tileset {}", tileset));
+        }
+
+        for variant in &self.variants {
+            lines.push(format!("variant {}", serialize_robot(&variant.robots[0])));
+            for robot in &variant.robots[1..] {
+                lines.push(format!("robot {}", serialize_robot(robot)));
+            }
+
+            let world = &variant.world;
+            for mover in &world.movers {
+                lines.push(serialize_mover(mover));
+            }
+
+            for (x, y) in &world.boxes {
+                lines.push(format!("box {} {}", x, y));
+            }
+
+            if let Some(timer) = world.gate_timer {
+                lines.push(format!("gate_timer {}", timer.period));
+            }
+
+            for y in 0..world.height {
+                lines.push(
+                    (0..world.width)
+                        .map(|x| world.get_tile((x, y)).to_char())
+                        .collect(),
+                );
+            }
+        }
+
+        lines.join("\n") + "\n"
+    }
+
+    /// A hash of the file of the level, which changes with anything that could change how a
+    /// script plays. Uses FNV-1a so it stays the same across builds and platforms
+    pub fn content_hash(&self) -> u64 {
+        self.serialize()
+            .bytes()
+            .fold(0xcbf29ce484222325, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The example at the top of this file
+    const EXAMPLE: &str = "id default
+par 22 12 4
+optimal 18
+require min_loops 1
+objective collect_gems
+fog
+tileset desert
+variant 1 1 right 20
+robot 1 2 right
+enemy 3 3 up
+block 2 3 left
+box 2 2
+gate_timer 4
+######
+#..*o#
+#...oE
+#...o#
+######
+";
+
+    fn error(source: &str) -> (usize, String) {
+        match Level::parse(source) {
+            Err(LevelError::Parse { line, message }) => (line, message),
+            Err(error) => panic!("unexpected error {}", error),
+            Ok(_) => panic!("parsed a broken level"),
+        }
+    }
+
+    #[test]
+    fn example_round_trip() {
+        let level = Level::parse(EXAMPLE).unwrap();
+
+        assert_eq!(level.id, "default");
+        assert_eq!(level.optimal_commands, Some(18));
+        assert_eq!(level.objectives, [Objective::CollectGems]);
+        assert_eq!(level.variants[0].robots.len(), 2);
+        assert_eq!(level.variants[0].world.movers.len(), 2);
+        assert_eq!(level.variants[0].world.boxes, [(2, 2)]);
+        assert_eq!(level.serialize(), EXAMPLE);
+    }
+
+    #[test]
+    fn round_trip_of_several_variants() {
+        let source = "id two
+par 10 5 0
+objective visit_checkpoints
+variant 1 1 down
+###
+#1#
+#2#
+#.#
+#E#
+variant 1 1 up
+####
+#.2E
+#1.#
+####
+";
+
+        let level = Level::parse(source).unwrap();
+
+        assert_eq!(level.variants.len(), 2);
+        assert_eq!(level.optimal_commands, None);
+        assert_eq!(level.serialize(), source);
+        assert_eq!(
+            Level::parse(&level.serialize()).unwrap().serialize(),
+            source
+        );
+    }
+
+    #[test]
+    fn missing_parts() {
+        assert_eq!(
+            error("par 1 1 1\nvariant 1 1 up\n###\n#.#\n###\n"),
+            (1, "level has no id".to_string())
+        );
+        assert_eq!(
+            error("id a\nvariant 1 1 up\n###\n#.#\n###\n"),
+            (1, "level has no par".to_string())
+        );
+        assert_eq!(
+            error("id a\npar 1 1 1\n"),
+            (1, "level has no variants".to_string())
+        );
+        assert_eq!(
+            error("id a\npar 1 1\n"),
+            (2, "expected a number".to_string())
+        );
+        assert_eq!(
+            error("id a\npar 1 1 1\n###\n"),
+            (3, "expected a keyword".to_string())
+        );
+    }
+
+    #[test]
+    fn broken_lines() {
+        assert_eq!(
+            error("id a\npar 1 1 1\nrequire min_cats 1\n"),
+            (3, "unknown requirement".to_string())
+        );
+        assert_eq!(
+            error("id a\npar 1 1 1\nvariant 1 1 sideways\n"),
+            (3, "expected left, right, up or down".to_string())
+        );
+        assert_eq!(
+            error("id a\npar 1 1 1\nvariant 1 1 up\n###\n#?#\n"),
+            (5, "unknown tile".to_string())
+        );
+        assert_eq!(
+            error("id a\npar 1 1 1\nvariant 1 1 up\ngate_timer 0\n"),
+            (4, "gate timer has to be 1 to 255".to_string())
+        );
+    }
+
+    #[test]
+    fn broken_variants() {
+        let variant = |robots: &str, rows: &str| {
+            error(&format!("id a\npar 1 1 1\nvariant {}\n{}", robots, rows))
+        };
+
+        assert_eq!(
+            variant("1 1 up", "###\n#.##\n###\n"),
+            (
+                3,
+                "rows of a variant have to be the same length".to_string()
+            )
+        );
+        assert_eq!(
+            variant("5 1 up", "###\n#.#\n###\n"),
+            (3, "robot is outside of the world".to_string())
+        );
+        assert_eq!(
+            variant("1 1 up\nrobot 1 1 down", "###\n#.#\n###\n"),
+            (3, "robots have to start on different tiles".to_string())
+        );
+        assert_eq!(
+            variant("1 1 up", "###\n#..\n###\n"),
+            (3, "the border has to be walls or exits".to_string())
+        );
+        assert_eq!(
+            variant("1 1 up", "#####\n#1.3#\n#####\n"),
+            (3, "checkpoints have to be numbered 1 to n".to_string())
+        );
+        assert_eq!(
+            variant("1 1 up", "#####\n#1.1#\n#####\n"),
+            (3, "checkpoints have to be numbered 1 to n".to_string())
+        );
+        assert_eq!(
+            variant("1 1 up", "###\n#~#\n###\n"),
+            (3, "robots can not start on this tile".to_string())
+        );
+        assert_eq!(
+            variant("1 1 up", "###\n#E#\n###\n"),
+            (3, "robots can not start on this tile".to_string())
+        );
+        assert_eq!(
+            variant("1 1 up\nbox 1 1", "###\n#.#\n###\n"),
+            (3, "boxes have to start on free tiles".to_string())
+        );
+    }
+}
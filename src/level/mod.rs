@@ -0,0 +1,394 @@
+use std::{fmt, io};
+
+use crate::{
+    interface::{
+        analysis::Metrics,
+        instructions::{simulate, Simulation},
+    },
+    robot::{Direction, Robot},
+    scoring::{score, Score},
+    solver::{solve, Solution},
+    team::Trace,
+    world::{
+        generator::{generate, GeneratorConfig, MazeStyle},
+        Tile, World, WorldError,
+    },
+};
+
+pub mod file;
+
+/// The par values a solution has to stay within to earn every star
+#[derive(Clone, Copy, Debug)]
+pub struct Par {
+    pub max_commands: usize,
+    pub max_lines: usize,
+    pub max_scans: usize,
+}
+
+/// A constraint on the structure of the script that has to hold for the level to count as completed
+#[derive(Clone, Copy, Debug)]
+pub enum Requirement {
+    MinFunctions(usize),
+    MinLoops(usize),
+    MinConditionals(usize),
+    MaxStatements(usize),
+    MaxDepth(usize),
+}
+
+impl Requirement {
+    pub fn is_met(&self, metrics: &Metrics) -> bool {
+        match *self {
+            Requirement::MinFunctions(count) => metrics.functions >= count,
+            Requirement::MinLoops(count) => metrics.loops >= count,
+            Requirement::MinConditionals(count) => metrics.conditionals >= count,
+            Requirement::MaxStatements(count) => metrics.statements <= count,
+            Requirement::MaxDepth(depth) => metrics.max_depth <= depth,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match *self {
+            Requirement::MinFunctions(count) => format!("Define at least {} function(s)", count),
+            Requirement::MinLoops(count) => format!("Use at least {} loop(s)", count),
+            Requirement::MinConditionals(count) => {
+                format!("Use at least {} if statement(s)", count)
+            }
+            Requirement::MaxStatements(count) => format!("Use at most {} statements", count),
+            Requirement::MaxDepth(depth) => format!("Nest at most {} level(s) deep", depth),
+        }
+    }
+}
+
+/// Something the robots have to do before reaching an exit counts as completing the level
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Objective {
+    /// Collect every gem in the world
+    CollectGems,
+    /// Visit the numbered checkpoints in order
+    VisitCheckpoints,
+    /// Push a box onto every goal
+    FillGoals,
+}
+
+impl Objective {
+    /// How far the robots got together, as done and total. Gems count for every robot, while
+    /// the checkpoints have to be visited by one robot
+    pub fn progress(&self, robots: &[Robot], world: &World) -> (usize, usize) {
+        match self {
+            Objective::CollectGems => {
+                let collected: usize = robots.iter().map(|robot| robot.gems as usize).sum();
+                let remaining = world.count_tiles(|tile| matches!(tile, Tile::Gem));
+
+                (collected, collected + remaining)
+            }
+            Objective::VisitCheckpoints => (
+                robots
+                    .iter()
+                    .map(|robot| robot.checkpoints as usize)
+                    .max()
+                    .unwrap_or(0),
+                world.count_tiles(|tile| matches!(tile, Tile::Checkpoint(_))),
+            ),
+            Objective::FillGoals => (
+                world
+                    .boxes
+                    .iter()
+                    .filter(|position| matches!(world.get_tile(**position), Tile::Goal))
+                    .count(),
+                world.count_tiles(|tile| matches!(tile, Tile::Goal)),
+            ),
+        }
+    }
+
+    pub fn is_met(&self, robots: &[Robot], world: &World) -> bool {
+        let (done, total) = self.progress(robots, world);
+
+        done >= total
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Objective::CollectGems => "Gems",
+            Objective::VisitCheckpoints => "Checkpoints",
+            Objective::FillGoals => "Goals",
+        }
+    }
+}
+
+/// Whether the robot with the given index stands on an exit with every objective met. In worlds
+/// without exits robots finish as soon as the objectives are met, if there are any
+pub fn has_finished(
+    objectives: &[Objective],
+    robots: &[Robot],
+    index: usize,
+    world: &World,
+) -> bool {
+    let reached_exit = match world.count_tiles(|tile| matches!(tile, Tile::Exit)) {
+        0 => !objectives.is_empty(),
+        _ => robots[index].is_on_end_tile(world),
+    };
+
+    reached_exit
+        && objectives
+            .iter()
+            .all(|objective| objective.is_met(robots, world))
+}
+
+/// One of the worlds a level is played in, a script has to solve every variant of a level
+#[derive(Clone)]
+pub struct Variant {
+    pub world: World,
+    /// The robots of the variant. Every one of them has to finish for the variant to be solved,
+    /// see `has_finished`
+    pub robots: Vec<Robot>,
+}
+
+#[derive(Clone)]
+pub struct Level {
+    pub id: String,
+    pub variants: Vec<Variant>,
+    pub par: Par,
+    /// The length of the shortest solution of the hardest variant, `None` if it was not searched
+    /// for or the search gave up. Searching takes long, so it is kept with the level
+    pub optimal_commands: Option<usize>,
+    pub requirements: Vec<Requirement>,
+    pub objectives: Vec<Objective>,
+    /// Hides the tiles during playback until a robot visited or scanned them
+    pub fog: bool,
+    /// The name of the tileset the level is drawn with instead of the default textures
+    pub tileset: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum LevelError {
+    Io(io::Error),
+    Parse {
+        line: usize,
+        message: String,
+    },
+    /// The exit of the variant with this index cannot be reached
+    Unsolvable(usize),
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::Io(error) => write!(f, "{}", error),
+            LevelError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            LevelError::Unsolvable(index) => write!(f, "variant {} cannot be solved", index + 1),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+impl Level {
+    /// Searches the shortest solution of every variant. Fails if a robot cannot reach an exit in
+    /// a variant, variants too large to search fully are assumed to be solvable
+    ///
+    /// Returns the length of the shortest solution of the hardest variant, if every search
+    /// finished
+    pub fn solve(&self) -> Result<Option<usize>, LevelError> {
+        let mut longest = Some(0);
+
+        for (index, variant) in self.variants.iter().enumerate() {
+            match solve(&variant.world, &variant.robots, &self.objectives) {
+                Solution::Found(trace) => longest = longest.map(|longest| longest.max(trace.len())),
+                Solution::Impossible => return Err(LevelError::Unsolvable(index)),
+                Solution::Unknown => longest = None,
+            }
+        }
+
+        Ok(longest)
+    }
+
+    /// Whether the level was generated by `practice_level`
+    pub fn is_practice(&self) -> bool {
+        self.id.starts_with("practice-")
+    }
+}
+
+/// Runs the scripts on every variant of the level. Without scripts every trace is empty
+pub fn run_level(level: &Level, scripts: &[Vec<u8>]) -> (Vec<Simulation>, Score) {
+    if scripts.is_empty() {
+        let runs: Vec<Simulation> = level
+            .variants
+            .iter()
+            .map(|_| Simulation {
+                trace: vec![],
+                output: vec![],
+                error: None,
+                load_error: None,
+            })
+            .collect();
+        let score = score(level, &vec![vec![]; level.variants.len()], &[]);
+
+        return (runs, score);
+    }
+
+    let runs: Vec<Simulation> = level
+        .variants
+        .iter()
+        .map(|variant| {
+            simulate(
+                &variant.robots,
+                variant.world.clone(),
+                level.objectives.clone(),
+                scripts,
+            )
+        })
+        .collect();
+    // The script of the first robot is the one that is scored
+    let traces: Vec<Trace> = runs.iter().map(|run| run.trace.clone()).collect();
+    let score = score(level, &traces, &scripts[0]);
+
+    (runs, score)
+}
+
+/// The world of a variant as it is shown during playback, covered where the robots did not
+/// explore yet if the level has fog
+pub fn playback_world(level: &Level, variant_index: usize) -> World {
+    let variant = &level.variants[variant_index];
+    let mut world = variant.world.clone();
+
+    if level.fog {
+        world.hide_unexplored();
+
+        for robot in &variant.robots {
+            world.reveal((robot.x, robot.y));
+        }
+    }
+
+    world
+}
+
+/// A world surrounded by walls, with an exit that may replace a part of the border
+pub fn bordered_world(resolution: (u8, u8), exit_position: (u8, u8)) -> Result<World, WorldError> {
+    let mut world = World::new(resolution);
+
+    for x in 0..world.width {
+        world.set_tile((x, 0), Tile::Wall)?;
+        world.set_tile((x, world.height - 1), Tile::Wall)?;
+    }
+
+    for y in 0..world.height {
+        world.set_tile((0, y), Tile::Wall)?;
+        world.set_tile((world.width - 1, y), Tile::Wall)?;
+    }
+
+    world.set_tile(exit_position, Tile::Exit)?;
+
+    Ok(world)
+}
+
+fn walled_world(walls: &[(u8, u8)]) -> Result<World, WorldError> {
+    let mut world = bordered_world((11, 11), (10, 5))?;
+
+    for position in walls {
+        world.set_tile(*position, Tile::Wall)?;
+    }
+
+    Ok(world)
+}
+
+/// The length of the shortest solution of the default level, which is too slow to search for
+/// every time the level is started
+const DEFAULT_OPTIMAL_COMMANDS: usize = 17;
+
+// TODO: Load levels from files instead of building them here
+pub fn default_level() -> Level {
+    // Every position fits into the 11x11 worlds
+    let first = walled_world(&[(5, 5), (7, 4), (5, 6), (6, 7), (3, 2)]).unwrap();
+    let second = walled_world(&[(4, 5), (6, 4), (6, 6), (8, 5), (2, 8)]).unwrap();
+
+    Level {
+        id: "default".to_string(),
+        variants: vec![
+            Variant {
+                world: first,
+                robots: vec![Robot::new(1, 5, Direction::Right)],
+            },
+            Variant {
+                world: second,
+                robots: vec![Robot::new(1, 5, Direction::Right)],
+            },
+        ],
+        par: Par {
+            max_commands: 22,
+            max_lines: 12,
+            max_scans: 4,
+        },
+        optimal_commands: Some(DEFAULT_OPTIMAL_COMMANDS),
+        requirements: vec![],
+        objectives: vec![],
+        fog: false,
+        tileset: None,
+    }
+}
+
+/// How much of the rooms and open practice levels are walls
+const PRACTICE_DENSITY: f32 = 0.3;
+
+/// A generated maze level for endless wall-following and search practice. Its id is
+/// `practice-<style>-<seed>`, so `practice_level_from_id` can generate it again
+pub fn practice_level(seed: u64, style: MazeStyle) -> Level {
+    let variants: Vec<Variant> = (0..3)
+        .map(|i| {
+            let (world, robot) = generate(&GeneratorConfig {
+                resolution: (11, 11),
+                density: PRACTICE_DENSITY,
+                style,
+                seed: seed.wrapping_add(i),
+            });
+
+            Variant {
+                world,
+                robots: vec![robot],
+            }
+        })
+        .collect();
+
+    let mut level = Level {
+        id: format!("practice-{}-{}", style.name().to_lowercase(), seed),
+        variants,
+        par: Par {
+            max_commands: 0,
+            max_lines: 20,
+            max_scans: 64,
+        },
+        optimal_commands: None,
+        requirements: vec![Requirement::MinLoops(1)],
+        objectives: vec![],
+        fog: false,
+        tileset: None,
+    };
+
+    // A general search script can't take the shortest path, so allow twice the optimal length
+    level.optimal_commands = level.solve().ok().flatten();
+    level.par.max_commands = level.optimal_commands.unwrap_or(64) * 2;
+
+    level
+}
+
+/// Generates the practice level with an id like `practice-rooms-42` again
+pub fn practice_level_from_id(id: &str) -> Option<Level> {
+    let (style, seed) = id.strip_prefix("practice-")?.split_once('-')?;
+    let style = MazeStyle::ALL
+        .into_iter()
+        .find(|candidate| candidate.name().to_lowercase() == style)?;
+
+    Some(practice_level(seed.parse().ok()?, style))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_level_has_its_optimal_solution() {
+        assert_eq!(
+            default_level().solve().unwrap(),
+            Some(DEFAULT_OPTIMAL_COMMANDS)
+        );
+    }
+}
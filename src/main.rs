@@ -1,14 +1,29 @@
 use app::run_app;
+use cli::Options;
 
 pub mod app;
+pub mod assets;
+pub mod cli;
+pub mod components;
+pub mod gif;
+pub mod heatmap;
 mod interface;
-pub mod robot;
-pub mod world;
-pub mod textures;
+pub mod level;
+pub mod paths;
+pub mod progress;
+pub mod recording;
 pub mod rendering;
+pub mod replay;
+pub mod robot;
+pub mod scoring;
 pub mod screens;
-pub mod components;
+pub mod solver;
+pub mod team;
+pub mod tileset;
+pub mod world;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    run_app()
+    let options = Options::parse(std::env::args().skip(1))?;
+
+    run_app(options)
 }
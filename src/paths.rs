@@ -0,0 +1,68 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// The folder the scripts are picked from, relative to the resource directory
+pub const SCRIPTS_DIRECTORY: &str = "lua";
+pub const DEFAULT_SCRIPT: &str = "lua/test.lua";
+/// The folder replays are saved to and picked from, relative to the resource directory
+pub const REPLAYS_DIRECTORY: &str = "replays";
+/// The folder levels are saved to from the editor and looked up in by their id, relative to the
+/// resource directory
+pub const LEVELS_DIRECTORY: &str = "levels";
+
+/// The directory the assets, fonts, scripts and saved files are in. This is the first directory
+/// with an `assets` folder out of the directory of the executable and its parents, so
+/// `cargo run` finds the assets of the project. Without an `assets` folder it is the directory
+/// of the executable, where the embedded assets are used and files are saved next to the game,
+/// and the working directory only if the executable can not be found
+fn resource_directory() -> &'static Path {
+    static DIRECTORY: OnceLock<PathBuf> = OnceLock::new();
+
+    DIRECTORY.get_or_init(|| {
+        let executable_directory = env::current_exe()
+            .ok()
+            .and_then(|path| path.parent().map(Path::to_path_buf));
+
+        executable_directory
+            .iter()
+            .flat_map(|directory| directory.ancestors().take(3))
+            .find(|directory| directory.join("assets").is_dir())
+            .map(Path::to_path_buf)
+            .or(executable_directory.clone())
+            .unwrap_or_else(|| PathBuf::from("."))
+    })
+}
+
+/// Resolves a path relative to the resource directory, no matter where the game was started from
+pub fn resource_path(relative: &str) -> String {
+    resource_directory()
+        .join(relative)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Adds a number to the name of a file, like `run-2.gif` for `run.gif`, to save one file for
+/// each variant of a level
+pub fn numbered_path(path: &Path, number: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+
+    path.with_file_name(match path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, number, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, number),
+    })
+}
+
+/// The path itself if there is no file there yet, and the first numbered path without a file
+/// otherwise, like `run-2.replay` for `run.replay`, so saving never replaces an earlier file
+pub fn unused_path(path: &Path) -> PathBuf {
+    match path.exists() {
+        false => path.to_path_buf(),
+        true => (2..)
+            .map(|number| numbered_path(path, number))
+            .find(|path| !path.exists())
+            .unwrap_or_else(|| path.to_path_buf()),
+    }
+}
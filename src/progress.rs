@@ -0,0 +1,56 @@
+use std::{collections::HashMap, fs};
+
+use crate::paths::resource_path;
+
+const PROGRESS_PATH: &str = "progress.txt";
+
+/// The best star rating reached on every level, stored as one `<level id> <stars>` line per level
+#[derive(Default)]
+pub struct Progress {
+    stars: HashMap<String, u8>,
+}
+
+impl Progress {
+    pub fn load() -> Self {
+        let Ok(contents) = fs::read_to_string(resource_path(PROGRESS_PATH)) else {
+            return Self::default();
+        };
+
+        let stars = contents
+            .lines()
+            .filter_map(|line| {
+                let (id, stars) = line.trim().rsplit_once(' ')?;
+                Some((id.to_string(), stars.parse().ok()?))
+            })
+            .collect();
+
+        Self { stars }
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let mut entries: Vec<_> = self.stars.iter().collect();
+        entries.sort();
+
+        let contents: String = entries
+            .into_iter()
+            .map(|(id, stars)| format!("{} {}\n", id, stars))
+            .collect();
+
+        fs::write(resource_path(PROGRESS_PATH), contents)
+    }
+
+    pub fn get_stars(&self, level_id: &str) -> u8 {
+        self.stars.get(level_id).copied().unwrap_or(0)
+    }
+
+    /// Stores the rating if it beats the previous best, returns whether it did
+    pub fn record(&mut self, level_id: &str, stars: u8) -> bool {
+        if stars <= self.get_stars(level_id) {
+            return false;
+        }
+
+        self.stars.insert(level_id.to_string(), stars);
+
+        true
+    }
+}
@@ -0,0 +1,141 @@
+use std::{fs, path::Path};
+
+use raylib::prelude::*;
+
+use crate::{
+    app::{tile_to_screen_pos, tile_to_screen_pos_centered},
+    assets::Assets,
+    components::trail::Trail,
+    gif::write_gif,
+    level::{playback_world, Level},
+    rendering::{export_png, Canvas, Drawable},
+    robot::{Robot, RobotCommand, RobotResponse},
+    team::{Team, Trace},
+    world::World,
+};
+
+/// How long every command is shown in hundredths of a second, as long as during playback
+const FRAME_DELAY: u16 = 25;
+/// How long the last frame is shown before the animation starts over
+const LAST_FRAME_DELAY: u16 = 200;
+
+/// Draws the world of a variant during playback with the robots and their trail
+pub fn draw_playback(
+    d: &mut RaylibDrawHandle,
+    assets: &Assets,
+    level: &Level,
+    world: &World,
+    robots: &[Robot],
+    trail: Option<&Trail>,
+) {
+    let tileset = level
+        .tileset
+        .as_deref()
+        .and_then(|name| assets.tileset(name));
+    world.draw_themed(d, assets, tileset);
+
+    if let Some(trail) = trail {
+        trail.draw((0, 0), d, assets);
+    }
+
+    for robot in robots {
+        robot.draw(tile_to_screen_pos_centered(robot.x, robot.y), d, assets);
+    }
+}
+
+/// Plays a trace of a variant back like the game does and draws a frame before the first command
+/// and after every command
+fn render_frames(
+    d: &mut RaylibDrawHandle,
+    assets: &Assets,
+    level: &Level,
+    variant_index: usize,
+    trace: &Trace,
+) -> Result<Vec<Image>, String> {
+    let variant = &level.variants[variant_index];
+    let mut world = playback_world(level, variant_index);
+    let mut team = Team::new(
+        variant.robots.clone(),
+        level.objectives.clone(),
+        &variant.world,
+    );
+    let mut trail = Trail::new(&variant.robots);
+
+    let size = tile_to_screen_pos(world.width, world.height);
+    let mut canvas = Canvas::new(size.0, size.1)?;
+    let mut draw_frame = |d: &mut RaylibDrawHandle, world: &World, team: &Team, trail: &Trail| {
+        canvas.draw(d, |d| {
+            d.clear_background(Color::BLACK);
+            draw_playback(d, assets, level, world, &team.robots, Some(trail));
+        })
+    };
+
+    let mut frames = vec![draw_frame(d, &world, &team, &trail)?];
+
+    for (index, command) in trace {
+        if matches!(command, RobotCommand::End) {
+            break;
+        }
+
+        let response = team.execute(*index, *command, &mut world);
+        trail.record(*index, *command, &team.robots[*index], &response);
+        frames.push(draw_frame(d, &world, &team, &trail)?);
+
+        if matches!(response, RobotResponse::Error(_)) || team.is_won() {
+            break;
+        }
+    }
+
+    Ok(frames)
+}
+
+/// Renders the playback of a trace and saves it as an animated GIF if the path ends in `.gif`,
+/// or as numbered PNG files in the directory at the path otherwise
+pub fn export_run(
+    d: &mut RaylibDrawHandle,
+    assets: &Assets,
+    level: &Level,
+    variant_index: usize,
+    trace: &Trace,
+    path: &Path,
+) -> Result<(), String> {
+    let frames = render_frames(d, assets, level, variant_index, trace)?;
+
+    match path.extension().is_some_and(|extension| extension == "gif") {
+        true => {
+            let pixels: Vec<Vec<Color>> = frames
+                .iter()
+                .map(|frame| frame.get_image_data().to_vec())
+                .collect();
+
+            let mut delays = vec![FRAME_DELAY; frames.len()];
+            if let Some(last) = delays.last_mut() {
+                *last = LAST_FRAME_DELAY;
+            }
+
+            if let Some(directory) = path.parent() {
+                fs::create_dir_all(directory)
+                    .map_err(|error| format!("Error writing {}: {}", path.display(), error))?;
+            }
+
+            write_gif(
+                path,
+                frames[0].width as u16,
+                frames[0].height as u16,
+                &pixels,
+                &delays,
+            )
+            .map_err(|error| format!("Error writing {}: {}", path.display(), error))
+        }
+        false => {
+            fs::create_dir_all(path)
+                .map_err(|error| format!("Error writing {}: {}", path.display(), error))?;
+
+            for (index, frame) in frames.iter().enumerate() {
+                export_png(frame, &path.join(format!("{:04}.png", index)))?;
+            }
+
+            Ok(())
+        }
+    }
+}
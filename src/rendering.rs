@@ -1,13 +1,63 @@
-use std::collections::HashMap;
+use std::path::Path;
 
-use raylib::{drawing::RaylibDrawHandle, text::Font, texture::Texture2D};
+use raylib::{
+    drawing::RaylibDrawHandle,
+    ffi,
+    texture::{Image, RaylibTexture2D, RenderTexture2D},
+};
+
+use crate::assets::Assets;
 
 pub trait Drawable {
-    fn draw(
-        &self,
-        position: (i32, i32),
+    fn draw(&self, position: (i32, i32), d: &mut RaylibDrawHandle, assets: &Assets);
+}
+
+/// Saves an image as a PNG file. raylib does not report errors, so this checks that the file
+/// was written
+pub fn export_png(image: &Image, path: &Path) -> Result<(), String> {
+    image.export_image(&path.to_string_lossy());
+
+    match path.is_file() {
+        true => Ok(()),
+        false => Err(format!("Error writing {}", path.display())),
+    }
+}
+
+/// A render texture that frames are drawn into to export them as images
+pub struct Canvas {
+    target: RenderTexture2D,
+}
+
+impl Canvas {
+    pub fn new(width: i32, height: i32) -> Result<Canvas, String> {
+        // raylib-rs only loads render textures through the RaylibHandle, which screens do not
+        // have during a frame
+        let target = unsafe { ffi::LoadRenderTexture(width, height) };
+
+        match target.id {
+            0 => Err("Could not create a render texture".to_string()),
+            _ => Ok(Canvas {
+                target: unsafe { RenderTexture2D::from_raw(target) },
+            }),
+        }
+    }
+
+    /// Draws into the canvas instead of the window during a frame and returns what was drawn
+    pub fn draw(
+        &mut self,
         d: &mut RaylibDrawHandle,
-        textures: &HashMap<String, Texture2D>,
-        fonts: &HashMap<String, Font>,
-    );
+        draw: impl FnOnce(&mut RaylibDrawHandle),
+    ) -> Result<Image, String> {
+        // raylib-rs only hands out a texture mode that the drawables can not draw with, so the
+        // drawing is redirected to the render texture directly
+        unsafe { ffi::BeginTextureMode(*self.target) };
+        draw(d);
+        unsafe { ffi::EndTextureMode() };
+
+        // Render textures are stored upside down
+        let mut image = self.target.load_image()?;
+        image.flip_vertical();
+
+        Ok(image)
+    }
 }
@@ -0,0 +1,481 @@
+use std::{fmt, fs, io, path::Path};
+
+use crate::{
+    interface::instructions::Simulation,
+    level::{default_level, practice_level_from_id, run_level, Level},
+    paths::{resource_path, LEVELS_DIRECTORY},
+    robot::RobotCommand,
+    scoring::Score,
+    team::Trace,
+};
+
+// A replay file names the level it was recorded on with the hash of its file, followed by the
+// stars the run earned, the script of every robot and the commands of every variant. `script`
+// lines give the number of lines of the script that follow them. `trace` lines list the
+// commands of a variant in the order they ran, each with the number of the robot that sent it:
+//
+// level default c96ed72a1216ba8d
+// stars 0
+// script 4
+// return function(robot)
+//     forward()
+//     left()
+// end
+// trace 1:forward 1:left
+// trace 1:forward 1:left
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    Parse {
+        line: usize,
+        message: String,
+    },
+    /// There is no level with this id
+    UnknownLevel(String),
+    /// The level with this id changed since the replay was recorded
+    LevelChanged(String),
+    /// A trace has commands of a robot the level does not have, with the number of the robot
+    UnknownRobot(usize),
+    /// Running the scripts again does not give the traces or stars of the replay
+    Tampered,
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(error) => write!(f, "{}", error),
+            ReplayError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            ReplayError::UnknownLevel(id) => write!(f, "there is no level {}", id),
+            ReplayError::LevelChanged(id) => {
+                write!(f, "the level {} changed since the replay was recorded", id)
+            }
+            ReplayError::UnknownRobot(robot) => write!(f, "the level has no robot {}", robot),
+            ReplayError::Tampered => write!(f, "the replay does not match its scripts"),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+fn parse_error<T>(line: usize, message: impl ToString) -> Result<T, ReplayError> {
+    Err(ReplayError::Parse {
+        line,
+        message: message.to_string(),
+    })
+}
+
+/// Parses a command with the number of the robot that sent it, like `2:forward`
+fn parse_command(line: usize, word: &str) -> Result<(usize, RobotCommand), ReplayError> {
+    let Some((robot, name)) = word.split_once(':') else {
+        return parse_error(line, format!("expected a robot and a command in {}", word));
+    };
+
+    let index = match robot.parse::<usize>() {
+        Ok(id) if id > 0 => id - 1,
+        _ => return parse_error(line, format!("unknown robot {}", robot)),
+    };
+
+    match RobotCommand::from_name(name) {
+        Some(command) => Ok((index, command)),
+        None => parse_error(line, format!("unknown command {}", name)),
+    }
+}
+
+/// The level with an id, out of the default level, the practice levels and the level files in
+/// the levels folder
+pub fn find_level(id: &str) -> Option<Level> {
+    if id == "default" {
+        return Some(default_level());
+    }
+
+    if let Some(level) = practice_level_from_id(id) {
+        return Some(level);
+    }
+
+    fs::read_dir(resource_path(LEVELS_DIRECTORY))
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "level")
+        })
+        .filter_map(|path| Level::load(path).ok())
+        .find(|level| level.id == id)
+}
+
+/// A run of a level that can be shared and checked against the level again
+pub struct Replay {
+    pub level_id: String,
+    /// The `content_hash` of the level the replay was recorded on
+    pub level_hash: u64,
+    /// The script of every robot
+    pub scripts: Vec<String>,
+    /// The commands of every variant of the level
+    pub traces: Vec<Trace>,
+    pub stars: u8,
+}
+
+impl Replay {
+    pub fn record(level: &Level, scripts: &[Vec<u8>], traces: Vec<Trace>, stars: u8) -> Replay {
+        Replay {
+            level_id: level.id.clone(),
+            level_hash: level.content_hash(),
+            scripts: scripts
+                .iter()
+                .map(|script| String::from_utf8_lossy(script).into_owned())
+                .collect(),
+            traces,
+            stars,
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Replay, ReplayError> {
+        let source = fs::read_to_string(path).map_err(ReplayError::Io)?;
+
+        Replay::parse(&source)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ReplayError> {
+        if let Some(directory) = path.as_ref().parent() {
+            fs::create_dir_all(directory).map_err(ReplayError::Io)?;
+        }
+
+        fs::write(path, self.serialize()).map_err(ReplayError::Io)
+    }
+
+    pub fn parse(source: &str) -> Result<Replay, ReplayError> {
+        let mut level = None;
+        let mut stars = None;
+        let mut scripts = vec![];
+        let mut traces = vec![];
+
+        let mut lines = source.lines().enumerate();
+
+        while let Some((index, line)) = lines.next() {
+            let line_number = index + 1;
+            let mut words = line.split_whitespace();
+
+            match words.next() {
+                None => continue,
+                Some("level") => {
+                    let id = words.next();
+                    let hash = words.next().map(|hash| u64::from_str_radix(hash, 16));
+
+                    match (id, hash) {
+                        (Some(id), Some(Ok(hash))) => level = Some((id.to_string(), hash)),
+                        _ => return parse_error(line_number, "expected an id and a hash"),
+                    }
+                }
+                Some("stars") => match words.next().map(str::parse) {
+                    Some(Ok(value)) => stars = Some(value),
+                    _ => return parse_error(line_number, "expected a number of stars"),
+                },
+                Some("script") => {
+                    let count: usize = match words.next().map(str::parse) {
+                        Some(Ok(count)) => count,
+                        _ => return parse_error(line_number, "expected a number of lines"),
+                    };
+
+                    let mut script = vec![];
+                    for _ in 0..count {
+                        match lines.next() {
+                            Some((_, line)) => script.push(line),
+                            None => {
+                                return parse_error(
+                                    line_number,
+                                    format!("expected {} lines of script", count),
+                                )
+                            }
+                        }
+                    }
+
+                    scripts.push(script.join("\n") + "\n");
+                }
+                Some("trace") => {
+                    let trace = words
+                        .map(|word| parse_command(line_number, word))
+                        .collect::<Result<Trace, ReplayError>>()?;
+
+                    traces.push(trace);
+                }
+                Some(keyword) => {
+                    return parse_error(line_number, format!("unknown keyword {}", keyword))
+                }
+            }
+        }
+
+        let Some((level_id, level_hash)) = level else {
+            return parse_error(1, "replay has no level");
+        };
+        let Some(stars) = stars else {
+            return parse_error(1, "replay has no stars");
+        };
+        if scripts.is_empty() {
+            return parse_error(1, "replay has no script");
+        }
+
+        Ok(Replay {
+            level_id,
+            level_hash,
+            scripts,
+            traces,
+            stars,
+        })
+    }
+
+    pub fn serialize(&self) -> String {
+        let mut lines = vec![
+            format!("level {} {:016x}", self.level_id, self.level_hash),
+            format!("stars {}", self.stars),
+        ];
+
+        for script in &self.scripts {
+            lines.push(format!("script {}", script.lines().count()));
+            lines.extend(script.lines().map(str::to_string));
+        }
+
+        for trace in &self.traces {
+            let commands = trace
+                .iter()
+                .map(|(index, command)| format!(" {}:{}", index + 1, command.name()));
+
+            lines.push(String::from("trace") + &commands.collect::<String>());
+        }
+
+        lines.join("\n") + "\n"
+    }
+
+    /// The scripts as the bytes the robots run
+    pub fn script_sources(&self) -> Vec<Vec<u8>> {
+        self.scripts
+            .iter()
+            .map(|script| script.as_bytes().to_vec())
+            .collect()
+    }
+
+    /// Checks that the replay was recorded on this version of the level and that running its
+    /// scripts again gives the same commands and stars. Returns the runs, so they can be played
+    /// back without running the scripts once more
+    pub fn verify(&self, level: &Level) -> Result<(Vec<Simulation>, Score), ReplayError> {
+        if level.id != self.level_id || level.content_hash() != self.level_hash {
+            return Err(ReplayError::LevelChanged(self.level_id.clone()));
+        }
+
+        for (variant, trace) in level.variants.iter().zip(&self.traces) {
+            if let Some((index, _)) = trace
+                .iter()
+                .find(|(index, _)| *index >= variant.robots.len())
+            {
+                return Err(ReplayError::UnknownRobot(index + 1));
+            }
+        }
+
+        let (runs, score) = run_level(level, &self.script_sources());
+        let same_traces = runs.iter().map(|run| &run.trace).eq(self.traces.iter());
+
+        match same_traces && score.stars == self.stars {
+            true => Ok((runs, score)),
+            false => Err(ReplayError::Tampered),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{level::practice_level, world::generator::MazeStyle};
+
+    /// The example at the top of this file
+    const EXAMPLE: &str = "level default c96ed72a1216ba8d
+stars 0
+script 4
+return function(robot)
+    forward()
+    left()
+end
+trace 1:forward 1:left
+trace 1:forward 1:left
+";
+
+    fn error(source: &str) -> (usize, String) {
+        match Replay::parse(source) {
+            Err(ReplayError::Parse { line, message }) => (line, message),
+            Err(error) => panic!("unexpected error {}", error),
+            Ok(_) => panic!("parsed a broken replay"),
+        }
+    }
+
+    #[test]
+    fn example_round_trip() {
+        let replay = Replay::parse(EXAMPLE).unwrap();
+
+        assert_eq!(replay.level_id, "default");
+        assert_eq!(replay.level_hash, 0xc96ed72a1216ba8d);
+        assert_eq!(replay.stars, 0);
+        assert_eq!(
+            replay.scripts,
+            ["return function(robot)\n    forward()\n    left()\nend\n"]
+        );
+        assert_eq!(
+            replay.traces[0],
+            [(0, RobotCommand::Forward), (0, RobotCommand::Left)]
+        );
+        assert_eq!(replay.serialize(), EXAMPLE);
+    }
+
+    #[test]
+    fn round_trip_of_several_robots() {
+        let replay = Replay {
+            level_id: "two".to_string(),
+            level_hash: 1,
+            scripts: vec![
+                "return function(robot)\n\n    scan()\nend\n".to_string(),
+                "-- trace 1:forward\n".to_string(),
+            ],
+            traces: vec![
+                vec![(0, RobotCommand::Scan), (1, RobotCommand::IsMarked)],
+                vec![],
+            ],
+            stars: 0,
+        };
+
+        let parsed = Replay::parse(&replay.serialize()).unwrap();
+
+        assert_eq!(parsed.level_hash, 1);
+        assert_eq!(parsed.scripts, replay.scripts);
+        assert_eq!(parsed.traces, replay.traces);
+        assert_eq!(parsed.serialize(), replay.serialize());
+    }
+
+    #[test]
+    fn missing_parts() {
+        assert_eq!(
+            error("stars 1\nscript 0\n"),
+            (1, "replay has no level".to_string())
+        );
+        assert_eq!(
+            error("level a 1\nscript 0\n"),
+            (1, "replay has no stars".to_string())
+        );
+        assert_eq!(
+            error("level a 1\nstars 1\n"),
+            (1, "replay has no script".to_string())
+        );
+        assert_eq!(
+            error("level a 1\nstars 1\nscript 3\nforward()\n"),
+            (3, "expected 3 lines of script".to_string())
+        );
+    }
+
+    #[test]
+    fn broken_lines() {
+        assert_eq!(
+            error("level a\n"),
+            (1, "expected an id and a hash".to_string())
+        );
+        assert_eq!(
+            error("level a xyz\n"),
+            (1, "expected an id and a hash".to_string())
+        );
+        assert_eq!(
+            error("level a 1\nstars many\n"),
+            (2, "expected a number of stars".to_string())
+        );
+        assert_eq!(
+            error("level a 1\nscore 3\n"),
+            (2, "unknown keyword score".to_string())
+        );
+    }
+
+    #[test]
+    fn example_is_of_the_default_level() {
+        let replay = Replay::parse(EXAMPLE).unwrap();
+
+        assert_eq!(replay.level_hash, default_level().content_hash());
+    }
+
+    #[test]
+    fn example_is_verified() {
+        let (runs, score) = Replay::parse(EXAMPLE)
+            .unwrap()
+            .verify(&default_level())
+            .unwrap();
+
+        assert_eq!(runs.len(), 2);
+        assert_eq!(score.stars, 0);
+    }
+
+    #[test]
+    fn robots_the_level_does_not_have_are_rejected() {
+        let mut replay = Replay::parse(EXAMPLE).unwrap();
+        replay.traces[1].push((1, RobotCommand::Forward));
+
+        assert!(matches!(
+            replay.verify(&default_level()),
+            Err(ReplayError::UnknownRobot(2))
+        ));
+    }
+
+    #[test]
+    fn scripts_can_not_reach_the_system() {
+        let marker = std::env::temp_dir().join("rust-navigator-replay-marker");
+        let level = default_level();
+
+        for call in [
+            format!("os.execute('touch {}')", marker.display()),
+            format!("io.open('{}', 'w'):close()", marker.display()),
+        ] {
+            let _ = fs::remove_file(&marker);
+
+            let replay = Replay {
+                level_id: level.id.clone(),
+                level_hash: level.content_hash(),
+                scripts: vec![format!(
+                    "return function(robot)\n    {}\n    forward()\nend\n",
+                    call
+                )],
+                traces: vec![vec![(0, RobotCommand::Forward)]; level.variants.len()],
+                stars: 0,
+            };
+
+            assert!(
+                matches!(replay.verify(&level), Err(ReplayError::Tampered)),
+                "{}",
+                call
+            );
+            assert!(!marker.exists(), "{}", call);
+        }
+    }
+
+    #[test]
+    fn practice_levels_are_found_again() {
+        for style in MazeStyle::ALL {
+            let level = practice_level(42, style);
+            let found = find_level(&level.id).unwrap();
+
+            assert_eq!(found.id, level.id);
+            assert_eq!(found.content_hash(), level.content_hash());
+        }
+
+        assert!(find_level("practice-spiral-42").is_none());
+        assert!(find_level("practice-rooms-many").is_none());
+    }
+
+    #[test]
+    fn broken_commands() {
+        assert_eq!(
+            error("trace 1:forward forward\n"),
+            (1, "expected a robot and a command in forward".to_string())
+        );
+        assert_eq!(
+            error("trace 0:forward\n"),
+            (1, "unknown robot 0".to_string())
+        );
+        assert_eq!(
+            error("trace 1:jump\n"),
+            (1, "unknown command jump".to_string())
+        );
+    }
+}
@@ -1,10 +1,13 @@
+use std::fmt;
+
 use crate::{
+    assets::{Assets, TextureKey},
     rendering::Drawable,
-    world::{World, WorldTile},
+    world::{Tile, TileEvent, World, WorldTile},
 };
 use raylib::prelude::*;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum RobotCommand {
     /// This command is sent by the program to tell the command receiver thread to shut down
     End,
@@ -13,16 +16,62 @@ pub enum RobotCommand {
     Left,
     Right,
     Scan,
+    PickUp,
+    Use,
+    /// Leaves a mark on the tile below the robot
+    Mark,
+    Unmark,
+    IsMarked,
+}
+
+impl RobotCommand {
+    /// The name of the command, like the function scripts call to send it
+    pub fn name(&self) -> &'static str {
+        match self {
+            RobotCommand::End => "end",
+            RobotCommand::Forward => "forward",
+            RobotCommand::Left => "left",
+            RobotCommand::Right => "right",
+            RobotCommand::Scan => "scan",
+            RobotCommand::PickUp => "pick_up",
+            RobotCommand::Use => "use",
+            RobotCommand::Mark => "mark",
+            RobotCommand::Unmark => "unmark",
+            RobotCommand::IsMarked => "is_marked",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<RobotCommand> {
+        match name {
+            "end" => Some(RobotCommand::End),
+            "forward" => Some(RobotCommand::Forward),
+            "left" => Some(RobotCommand::Left),
+            "right" => Some(RobotCommand::Right),
+            "scan" => Some(RobotCommand::Scan),
+            "pick_up" => Some(RobotCommand::PickUp),
+            "use" => Some(RobotCommand::Use),
+            "mark" => Some(RobotCommand::Mark),
+            "unmark" => Some(RobotCommand::Unmark),
+            "is_marked" => Some(RobotCommand::IsMarked),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum RobotResponse {
     Ok,
-    Error,
+    Error(RobotError),
+    /// The robot reached the exit, nothing is run after this
+    Finished,
     Scan(bool),
+    /// Whether picking up or using something had an effect
+    Action(bool),
+    /// Whether the tile below the robot is marked
+    Marked(bool),
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Direction {
     Left,
     Right,
@@ -30,18 +79,112 @@ pub enum Direction {
     Down,
 }
 
-#[derive(Debug)]
+impl Direction {
+    /// The position next to `position` in this direction
+    pub fn offset(&self, position: (u8, u8)) -> (u8, u8) {
+        match self {
+            Direction::Left => (position.0 - 1, position.1),
+            Direction::Right => (position.0 + 1, position.1),
+            Direction::Up => (position.0, position.1 - 1),
+            Direction::Down => (position.0, position.1 + 1),
+        }
+    }
+
+    pub fn clockwise(&self) -> Direction {
+        match self {
+            Direction::Left => Direction::Up,
+            Direction::Right => Direction::Down,
+            Direction::Up => Direction::Right,
+            Direction::Down => Direction::Left,
+        }
+    }
+
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+        }
+    }
+
+    pub fn get_draw_rotation(&self) -> f32 {
+        match self {
+            Direction::Left => 180.0,
+            Direction::Right => 0.0,
+            Direction::Up => 270.0,
+            Direction::Down => 90.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RobotError {
     InvalidMove(u8, u8),
+    /// Drove into another robot, an enemy or a block
+    Collided(u8, u8),
+    /// An enemy moved onto the robot
+    Caught(u8, u8),
+    /// Tried to push a box into something
+    BoxBlocked(u8, u8),
+    Drowned(u8, u8),
+    Melted(u8, u8),
+    BatteryEmpty,
+    TooManyCommands,
+}
+
+impl fmt::Display for RobotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RobotError::InvalidMove(x, y) => write!(f, "Crashed into ({}, {})", x, y),
+            RobotError::Collided(x, y) => write!(f, "Collided with something at ({}, {})", x, y),
+            RobotError::Caught(x, y) => write!(f, "Caught by an enemy at ({}, {})", x, y),
+            RobotError::BoxBlocked(x, y) => write!(f, "Could not push the box at ({}, {})", x, y),
+            RobotError::Drowned(x, y) => write!(f, "Drowned at ({}, {})", x, y),
+            RobotError::Melted(x, y) => write!(f, "Melted in lava at ({}, {})", x, y),
+            RobotError::BatteryEmpty => write!(f, "Ran out of battery"),
+            RobotError::TooManyCommands => write!(f, "Used too many commands"),
+        }
+    }
+}
+
+impl std::error::Error for RobotError {}
+
+impl RobotError {
+    /// The tile the robot failed at, if the error happened at one
+    pub fn position(&self) -> Option<(u8, u8)> {
+        match self {
+            RobotError::InvalidMove(x, y)
+            | RobotError::Collided(x, y)
+            | RobotError::Caught(x, y)
+            | RobotError::BoxBlocked(x, y)
+            | RobotError::Drowned(x, y)
+            | RobotError::Melted(x, y) => Some((*x, *y)),
+            RobotError::BatteryEmpty | RobotError::TooManyCommands => None,
+        }
+    }
 }
 
 type Result<T> = std::result::Result<T, RobotError>;
 
-#[derive(Clone, Copy)]
+/// Every move forward costs one charge, chargers fill the battery up again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Battery {
+    pub charge: u16,
+    pub capacity: u16,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Robot {
     pub x: u8,
     pub y: u8,
     pub scanning: bool,
+    pub keys: u8,
+    pub gems: u8,
+    /// How many checkpoints were visited in order
+    pub checkpoints: u8,
+    /// Robots without a battery can move forever
+    pub battery: Option<Battery>,
     facing: Direction,
 }
 
@@ -51,12 +194,30 @@ impl Robot {
             x,
             y,
             scanning: false,
+            keys: 0,
+            gems: 0,
+            checkpoints: 0,
+            battery: None,
             facing: orientation,
         }
     }
 
-    pub fn forward(&mut self, world: &World) -> Result<()> {
-        let forward_position = self.get_forward_position();
+    pub fn with_battery(mut self, capacity: u16) -> Self {
+        self.battery = Some(Battery {
+            charge: capacity,
+            capacity,
+        });
+
+        self
+    }
+
+    /// * `others`: The positions of the other robots and movers in the world, they block the way
+    ///   like walls
+    pub fn forward(&mut self, world: &mut World, others: &[(u8, u8)]) -> Result<()> {
+        // Driving off the edge of a world without a border crashes like driving into a wall
+        let Some(forward_position) = self.get_forward_position(world) else {
+            return Err(RobotError::InvalidMove(self.x, self.y));
+        };
 
         if world.get_tile(forward_position).collision() {
             return Err(RobotError::InvalidMove(
@@ -65,12 +226,141 @@ impl Robot {
             ));
         }
 
-        self.x = forward_position.0;
-        self.y = forward_position.1;
+        if others.contains(&forward_position) {
+            return Err(RobotError::Collided(forward_position.0, forward_position.1));
+        }
+
+        if let Some(battery) = &mut self.battery {
+            if battery.charge == 0 {
+                return Err(RobotError::BatteryEmpty);
+            }
+
+            battery.charge -= 1;
+        }
+
+        // Boxes are pushed along, but only one at a time
+        if world.has_box(forward_position) && !world.push_box(forward_position, self.facing, others)
+        {
+            return Err(RobotError::BoxBlocked(
+                forward_position.0,
+                forward_position.1,
+            ));
+        }
+
+        self.move_to(forward_position, world)?;
+
+        // Ice and conveyors keep moving the robot on their own, a loop of conveyors is stopped
+        // once it could have visited every tile
+        for _ in 0..world.width as usize * world.height as usize {
+            let direction = match world.get_tile((self.x, self.y)) {
+                Tile::Ice => self.facing,
+                Tile::Conveyor(direction) => direction,
+                _ => break,
+            };
+
+            let Some(next_position) = world.neighbour((self.x, self.y), direction) else {
+                break;
+            };
+
+            if world.get_tile(next_position).collision()
+                || world.has_box(next_position)
+                || others.contains(&next_position)
+            {
+                break;
+            }
+
+            self.move_to(next_position, world)?;
+        }
 
         Ok(())
     }
 
+    /// Moves the robot onto a tile and applies what happens when it leaves the previous tile and
+    /// enters the new one
+    fn move_to(&mut self, position: (u8, u8), world: &mut World) -> Result<()> {
+        let previous_position = (self.x, self.y);
+
+        self.x = position.0;
+        self.y = position.1;
+        world.reveal(position);
+
+        if let Some(event) = world.get_tile(previous_position).on_leave() {
+            world.apply_event(previous_position, event);
+        }
+
+        if let Some(event) = world.get_tile(position).on_enter() {
+            world.apply_event(position, event);
+        }
+
+        match world.get_tile(position) {
+            Tile::Water => Err(RobotError::Drowned(position.0, position.1)),
+            Tile::Lava => Err(RobotError::Melted(position.0, position.1)),
+            Tile::Charger => {
+                if let Some(battery) = &mut self.battery {
+                    battery.charge = battery.capacity;
+                }
+
+                Ok(())
+            }
+            Tile::Gem => {
+                self.pick_up(world);
+                Ok(())
+            }
+            Tile::Checkpoint(number) => {
+                if number == self.checkpoints + 1 {
+                    self.checkpoints = number;
+                }
+
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Runs a command and applies the changes it makes to the world
+    pub fn execute(
+        &mut self,
+        command: RobotCommand,
+        world: &mut World,
+        others: &[(u8, u8)],
+    ) -> RobotResponse {
+        self.scanning = false;
+
+        match command {
+            RobotCommand::Forward => match self.forward(world, others) {
+                Ok(()) => RobotResponse::Ok,
+                Err(error) => RobotResponse::Error(error),
+            },
+            RobotCommand::Left => {
+                self.left();
+                RobotResponse::Ok
+            }
+            RobotCommand::Right => {
+                self.right();
+                RobotResponse::Ok
+            }
+            RobotCommand::Scan => {
+                self.scanning = true;
+                if let Some(position) = self.get_forward_position(world) {
+                    world.reveal(position);
+                }
+                RobotResponse::Scan(self.scan(world, others))
+            }
+            RobotCommand::PickUp => RobotResponse::Action(self.pick_up(world)),
+            RobotCommand::Use => RobotResponse::Action(self.use_tile(world)),
+            RobotCommand::Mark => {
+                world.set_marked((self.x, self.y), true);
+                RobotResponse::Ok
+            }
+            RobotCommand::Unmark => {
+                world.set_marked((self.x, self.y), false);
+                RobotResponse::Ok
+            }
+            RobotCommand::IsMarked => RobotResponse::Marked(world.is_marked((self.x, self.y))),
+            RobotCommand::End => RobotResponse::Ok,
+        }
+    }
+
     pub fn left(&mut self) {
         self.facing = match self.facing {
             Direction::Left => Direction::Down,
@@ -81,38 +371,74 @@ impl Robot {
     }
 
     pub fn right(&mut self) {
-        self.facing = match self.facing {
-            Direction::Left => Direction::Up,
-            Direction::Right => Direction::Down,
-            Direction::Up => Direction::Right,
-            Direction::Down => Direction::Left,
-        }
+        self.facing = self.facing.clockwise();
+    }
+
+    pub fn get_facing(&self) -> Direction {
+        self.facing
+    }
+
+    /// The tile in front of the robot, `None` if it faces the edge of the world
+    pub fn get_forward_position(&self, world: &World) -> Option<(u8, u8)> {
+        world.neighbour((self.x, self.y), self.facing)
+    }
+
+    pub fn scan(&mut self, world: &World, others: &[(u8, u8)]) -> bool {
+        // The edge of the world blocks the way like a wall
+        let Some(forward_position) = self.get_forward_position(world) else {
+            return true;
+        };
+
+        world.get_tile(forward_position).collision()
+            || world.has_box(forward_position)
+            || others.contains(&forward_position)
     }
 
-    pub fn get_forward_position(&self) -> (u8, u8) {
-        match self.facing {
-            Direction::Left => (self.x - 1, self.y),
-            Direction::Right => (self.x + 1, self.y),
-            Direction::Up => (self.x, self.y - 1),
-            Direction::Down => (self.x, self.y + 1),
+    /// Picks up whatever lies on the tile below the robot
+    pub fn pick_up(&mut self, world: &mut World) -> bool {
+        let position = (self.x, self.y);
+        let tile = world.get_tile(position);
+
+        match tile.on_pick_up() {
+            Some(remaining) => {
+                match tile {
+                    Tile::Key => self.keys = self.keys.saturating_add(1),
+                    Tile::Gem => self.gems = self.gems.saturating_add(1),
+                    _ => (),
+                }
+
+                world.apply_event(position, TileEvent::Replace(remaining));
+                true
+            }
+            None => false,
         }
     }
 
-    pub fn scan(&mut self, world: &World) -> bool {
-        world.get_tile(self.get_forward_position()).collision()
+    /// Uses the tile in front of the robot
+    pub fn use_tile(&mut self, world: &mut World) -> bool {
+        let Some(position) = self.get_forward_position(world) else {
+            return false;
+        };
+
+        match world.get_tile(position).on_use(self.keys) {
+            Some((event, used_key)) => {
+                if used_key {
+                    self.keys -= 1;
+                }
+
+                world.apply_event(position, event);
+                true
+            }
+            None => false,
+        }
     }
 
     pub fn is_on_end_tile(&self, world: &World) -> bool {
-        self.x == world.exit_position.0 && self.y == world.exit_position.1
+        matches!(world.get_tile((self.x, self.y)), Tile::Exit)
     }
 
     pub fn get_draw_rotation(&self) -> f32 {
-        match self.facing {
-            Direction::Left => 180.0,
-            Direction::Right => 0.0,
-            Direction::Up => 270.0,
-            Direction::Down => 90.0,
-        }
+        self.facing.get_draw_rotation()
     }
 }
 
@@ -121,11 +447,10 @@ impl Drawable for Robot {
         &self,
         position: (i32, i32),
         d: &mut raylib::prelude::RaylibDrawHandle,
-        textures: &std::collections::HashMap<String, raylib::texture::Texture2D>,
-        _fonts: &std::collections::HashMap<String, Font>,
+        assets: &Assets,
     ) {
         d.draw_texture_pro(
-            textures.get("rover").unwrap(),
+            assets.texture(TextureKey::Rover),
             Rectangle {
                 x: 0.0,
                 y: 0.0,
@@ -0,0 +1,115 @@
+use crate::{
+    interface::analysis::{analyze, count_code_lines},
+    level::{Level, Objective, Requirement, Variant},
+    robot::{RobotCommand, RobotResponse},
+    team::{Team, Trace},
+};
+
+pub const MAX_STARS: u8 = 3;
+
+#[derive(Clone, Debug)]
+pub struct Score {
+    /// Whether every robot reached an exit in every variant and the script met every
+    /// requirement of the level
+    pub completed: bool,
+    /// Whether every robot reached an exit, for each variant of the level
+    pub solved_variants: Vec<bool>,
+    /// The most commands used in any variant, counting the commands of every robot
+    pub commands: usize,
+    pub lines: usize,
+    /// The most scans used in any variant
+    pub scans: usize,
+    /// The length of the shortest solution of the hardest variant
+    pub optimal_commands: Option<usize>,
+    /// 0 if the level was not completed, 1 for reaching the exit, 2 if the command par was met
+    /// as well and 3 if every par was met
+    pub stars: u8,
+    pub unmet_requirements: Vec<Requirement>,
+}
+
+/// * `runs`: The trace recorded for each variant of the level
+pub fn score(level: &Level, runs: &[Trace], source: &[u8]) -> Score {
+    // A script that cannot be analyzed does not meet any requirement
+    let metrics = analyze(source).ok();
+    let unmet_requirements: Vec<Requirement> = level
+        .requirements
+        .iter()
+        .filter(|requirement| match &metrics {
+            Some(metrics) => !requirement.is_met(metrics),
+            None => true,
+        })
+        .copied()
+        .collect();
+
+    let solved_variants: Vec<bool> = level
+        .variants
+        .iter()
+        .zip(runs)
+        .map(|(variant, trace)| is_won(variant, trace, &level.objectives))
+        .collect();
+
+    let completed = !solved_variants.is_empty()
+        && solved_variants.iter().all(|solved| *solved)
+        && unmet_requirements.is_empty();
+    // A script that cannot be tokenized counts every line that is not blank
+    let lines = count_code_lines(source).unwrap_or_else(|_| {
+        String::from_utf8_lossy(source)
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .count()
+    });
+    let scans = runs
+        .iter()
+        .map(|trace| {
+            trace
+                .iter()
+                .filter(|(_, command)| matches!(command, RobotCommand::Scan))
+                .count()
+        })
+        .max()
+        .unwrap_or(0);
+    let commands = runs
+        .iter()
+        .map(|trace| {
+            trace
+                .iter()
+                .filter(|(_, command)| !matches!(command, RobotCommand::End))
+                .count()
+        })
+        .max()
+        .unwrap_or(0);
+
+    let commands_par = commands <= level.par.max_commands;
+    let source_par = lines <= level.par.max_lines && scans <= level.par.max_scans;
+
+    let stars = match (completed, commands_par, source_par) {
+        (false, _, _) => 0,
+        (true, true, true) => 3,
+        (true, true, false) => 2,
+        (true, false, _) => 1,
+    };
+
+    Score {
+        completed,
+        solved_variants,
+        commands,
+        optimal_commands: level.optimal_commands,
+        lines,
+        scans,
+        stars,
+        unmet_requirements,
+    }
+}
+
+fn is_won(variant: &Variant, trace: &Trace, objectives: &[Objective]) -> bool {
+    let mut world = variant.world.clone();
+    let mut team = Team::new(variant.robots.clone(), objectives.to_vec(), &world);
+
+    for (index, command) in trace {
+        if let RobotResponse::Error(_) = team.execute(*index, *command, &mut world) {
+            return false;
+        }
+    }
+
+    team.is_won()
+}
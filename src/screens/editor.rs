@@ -1,2 +1,621 @@
-pub fn update_editor_screen() {
+use std::path::Path;
+
+use raylib::prelude::*;
+
+use crate::{
+    app::{screen_to_tile_pos, tile_to_screen_pos, tile_to_screen_pos_centered},
+    assets::Assets,
+    level::{bordered_world, Level, Objective, Par, Variant},
+    paths::{resource_path, unused_path, LEVELS_DIRECTORY},
+    rendering::Drawable,
+    robot::{Direction, Robot},
+    world::{
+        dynamic::{Mover, MoverKind},
+        Tile,
+    },
+};
+
+use super::{game::GameScreen, menu::MenuScreen, Screen};
+
+// The window fits 11 tiles in each direction
+const MIN_SIZE: u8 = 3;
+const MAX_SIZE: u8 = 11;
+const MAX_ROBOTS: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Brush {
+    Ground,
+    Wall,
+    Exit,
+    Robot,
+    Door,
+    Key,
+    Switch,
+    Gate,
+    PressurePlate,
+    Water,
+    Lava,
+    Ice,
+    Conveyor(Direction),
+    Charger,
+    Gem,
+    Checkpoint,
+    /// Adds another robot
+    ExtraRobot,
+    Enemy,
+    Block,
+    Box,
+    Goal,
+}
+
+impl Brush {
+    /// Every brush in the order the mouse wheel cycles through them, the first nine are also
+    /// selected with the number keys
+    const ALL: [Brush; 24] = [
+        Brush::Ground,
+        Brush::Wall,
+        Brush::Exit,
+        Brush::Robot,
+        Brush::Door,
+        Brush::Key,
+        Brush::Switch,
+        Brush::Gate,
+        Brush::PressurePlate,
+        Brush::Water,
+        Brush::Lava,
+        Brush::Ice,
+        Brush::Conveyor(Direction::Right),
+        Brush::Conveyor(Direction::Down),
+        Brush::Conveyor(Direction::Left),
+        Brush::Conveyor(Direction::Up),
+        Brush::Charger,
+        Brush::Gem,
+        Brush::Checkpoint,
+        Brush::ExtraRobot,
+        Brush::Enemy,
+        Brush::Block,
+        Brush::Box,
+        Brush::Goal,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Brush::Ground => "Ground",
+            Brush::Wall => "Wall",
+            Brush::Exit => "Exit",
+            Brush::Robot => "Robot",
+            Brush::Door => "Door",
+            Brush::Key => "Key",
+            Brush::Switch => "Switch",
+            Brush::Gate => "Gate",
+            Brush::PressurePlate => "Plate",
+            Brush::Water => "Water",
+            Brush::Lava => "Lava",
+            Brush::Ice => "Ice",
+            Brush::Conveyor(Direction::Right) => "Conveyor >",
+            Brush::Conveyor(Direction::Down) => "Conveyor v",
+            Brush::Conveyor(Direction::Left) => "Conveyor <",
+            Brush::Conveyor(Direction::Up) => "Conveyor ^",
+            Brush::Charger => "Charger",
+            Brush::Gem => "Gem",
+            Brush::Checkpoint => "Checkpoint",
+            Brush::ExtraRobot => "Robot +",
+            Brush::Enemy => "Enemy",
+            Brush::Block => "Block",
+            Brush::Box => "Box",
+            Brush::Goal => "Goal",
+        }
+    }
+}
+
+pub struct EditorScreen {
+    level: Level,
+    variant_index: usize,
+    brush: Brush,
+    status: String,
+    status_color: Color,
+    script_path: String,
+    /// Whether the editor was left to test-play the level instead of going back to the menu
+    playing: bool,
+}
+
+impl EditorScreen {
+    /// New levels are named after the first `custom` level file that does not exist yet, like
+    /// `custom-2`, so they do not replace the levels saved before
+    ///
+    /// * `script_path`: The script the level is test-played with
+    pub fn new(script_path: String) -> Self {
+        let path = unused_path(Path::new(&level_path("custom")));
+        let id = path.file_stem().unwrap_or_default().to_string_lossy();
+
+        Self::with_level(
+            Level {
+                id: id.into_owned(),
+                variants: vec![Variant {
+                    world: bordered_world((MAX_SIZE, MAX_SIZE), (MAX_SIZE - 2, MAX_SIZE / 2))
+                        .unwrap(),
+                    robots: vec![Robot::new(1, MAX_SIZE / 2, Direction::Right)],
+                }],
+                par: Par {
+                    max_commands: 0,
+                    max_lines: 20,
+                    max_scans: 20,
+                },
+                requirements: vec![],
+                objectives: vec![],
+                fog: false,
+                tileset: None,
+                optimal_commands: None,
+            },
+            script_path,
+        )
+    }
+
+    pub fn with_level(level: Level, script_path: String) -> Self {
+        Self {
+            level,
+            script_path,
+            variant_index: 0,
+            brush: Brush::Wall,
+            playing: false,
+            status: "[1-9/Wheel] Brush [B]attery [N]ew [Enter]".to_string(),
+            status_color: Color::WHITE,
+        }
+    }
+
+    fn set_status(&mut self, status: impl ToString, color: Color) {
+        self.status = status.to_string();
+        self.status_color = color;
+    }
+
+    /// The current variant to change it, which makes the best solution unknown until the next check
+    fn variant(&mut self) -> &mut Variant {
+        self.level.optimal_commands = None;
+
+        &mut self.level.variants[self.variant_index]
+    }
+
+    fn paint(&mut self, position: (u8, u8), brush: Brush) {
+        let variant = self.variant();
+
+        // The border stays closed so robots cannot leave the world, exits may replace a part of it
+        if variant.world.is_border(position) && !matches!(brush, Brush::Wall | Brush::Exit) {
+            return;
+        }
+
+        let robot_index = variant
+            .robots
+            .iter()
+            .position(|robot| (robot.x, robot.y) == position);
+        let occupied = robot_index.is_some()
+            || variant.world.mover_at(position).is_some()
+            || variant.world.has_box(position);
+        let can_start_on = variant.world.get_tile(position).can_start_on();
+
+        let tile = match brush {
+            Brush::Ground => {
+                // Clearing a tile removes the robots on it, except for the first one
+                if let Some(index @ 1..) = robot_index {
+                    variant.robots.remove(index);
+                }
+
+                variant
+                    .world
+                    .movers
+                    .retain(|mover| (mover.x, mover.y) != position);
+                variant.world.boxes.retain(|b| *b != position);
+
+                Tile::Ground
+            }
+            Brush::Wall => Tile::Wall,
+            Brush::Exit => Tile::Exit,
+            Brush::Robot | Brush::ExtraRobot if !can_start_on => {
+                self.set_status("Robots can not start on this tile", Color::RED);
+                return;
+            }
+            Brush::Robot => {
+                if !occupied {
+                    variant.robots[0].x = position.0;
+                    variant.robots[0].y = position.1;
+                }
+
+                return;
+            }
+            Brush::ExtraRobot => {
+                if !occupied && variant.robots.len() < MAX_ROBOTS {
+                    let mut robot = variant.robots[0];
+                    robot.x = position.0;
+                    robot.y = position.1;
+                    variant.robots.push(robot);
+                }
+
+                return;
+            }
+            Brush::Enemy | Brush::Block => {
+                let kind = match brush {
+                    Brush::Enemy => MoverKind::Enemy,
+                    _ => MoverKind::Block,
+                };
+
+                if !occupied {
+                    let mover = Mover::new(position.0, position.1, kind, Direction::Right);
+                    if let Err(error) = variant.world.add_mover(mover) {
+                        self.set_status(error, Color::RED);
+                    }
+                }
+
+                return;
+            }
+            Brush::Box => {
+                if !occupied {
+                    if let Err(error) = variant.world.add_box(position) {
+                        self.set_status(error, Color::RED);
+                    }
+                }
+
+                return;
+            }
+            Brush::Goal => Tile::Goal,
+            Brush::Door => Tile::Door { locked: true },
+            Brush::Key => Tile::Key,
+            Brush::Switch => Tile::Switch,
+            Brush::Gate => Tile::Gate { open: false },
+            Brush::PressurePlate => Tile::PressurePlate,
+            Brush::Water => Tile::Water,
+            Brush::Lava => Tile::Lava,
+            Brush::Ice => Tile::Ice,
+            Brush::Conveyor(direction) => Tile::Conveyor(direction),
+            Brush::Charger => Tile::Charger,
+            Brush::Gem => Tile::Gem,
+            // Checkpoints are numbered in the order they are placed
+            Brush::Checkpoint => {
+                if let Tile::Checkpoint(_) = variant.world.get_tile(position) {
+                    return;
+                }
+
+                let count = variant
+                    .world
+                    .count_tiles(|tile| matches!(tile, Tile::Checkpoint(_)));
+                if count >= 9 {
+                    return;
+                }
+
+                Tile::Checkpoint(count as u8 + 1)
+            }
+        };
+
+        // Robots keep a tile they can start on under them
+        if robot_index.is_some() && !tile.can_start_on() {
+            self.set_status("Robots can not start on this tile", Color::RED);
+            return;
+        }
+
+        let result = variant.world.set_tile(position, tile);
+        // Painting over a checkpoint would leave a gap in the numbers
+        variant.world.renumber_checkpoints();
+
+        if let Err(error) = result {
+            self.set_status(error, Color::RED);
+        }
+    }
+
+    fn cycle_brush(&mut self, offset: i32) {
+        let count = Brush::ALL.len() as i32;
+        let index = Brush::ALL
+            .iter()
+            .position(|brush| *brush == self.brush)
+            .unwrap_or(0) as i32;
+
+        self.brush = Brush::ALL[(index + offset).rem_euclid(count) as usize];
+    }
+
+    /// Steps through no battery and capacities of 10 to 50, for every robot of the variant
+    fn cycle_battery(&mut self) {
+        let capacity = match self.variant().robots[0].battery {
+            None => Some(10),
+            Some(battery) if battery.capacity < 50 => Some(battery.capacity / 10 * 10 + 10),
+            Some(_) => None,
+        };
+
+        for robot in self.variant().robots.iter_mut() {
+            let without_battery = Robot::new(robot.x, robot.y, robot.get_facing());
+            *robot = match capacity {
+                Some(capacity) => without_battery.with_battery(capacity),
+                None => without_battery,
+            };
+        }
+
+        match capacity {
+            Some(capacity) => self.set_status(format!("Battery {}", capacity), Color::WHITE),
+            None => self.set_status("No battery", Color::WHITE),
+        }
+    }
+
+    /// Steps through every combination of objectives
+    fn cycle_objectives(&mut self) {
+        self.level.optimal_commands = None;
+        let objectives = &mut self.level.objectives;
+
+        *objectives = match objectives[..] {
+            [] => vec![Objective::CollectGems],
+            [Objective::CollectGems] => vec![Objective::VisitCheckpoints],
+            [Objective::VisitCheckpoints] => {
+                vec![Objective::CollectGems, Objective::VisitCheckpoints]
+            }
+            [Objective::CollectGems, Objective::VisitCheckpoints] => vec![Objective::FillGoals],
+            _ => vec![],
+        };
+
+        let names: Vec<&str> = self
+            .level
+            .objectives
+            .iter()
+            .map(Objective::describe)
+            .collect();
+        match names.is_empty() {
+            true => self.set_status("No objectives", Color::WHITE),
+            false => self.set_status(format!("Objectives: {}", names.join(", ")), Color::WHITE),
+        }
+    }
+
+    fn resize(&mut self, width_change: i32, height_change: i32) {
+        let variant = self.variant();
+        let width =
+            (variant.world.width as i32 + width_change).clamp(MIN_SIZE as i32, MAX_SIZE as i32);
+        let height =
+            (variant.world.height as i32 + height_change).clamp(MIN_SIZE as i32, MAX_SIZE as i32);
+
+        variant.world = variant.world.resized((width as u8, height as u8));
+        variant.world.close_border();
+        variant.world.renumber_checkpoints();
+
+        // Robots stay inside the border
+        for robot in variant.robots.iter_mut() {
+            robot.x = robot.x.clamp(1, width as u8 - 2);
+            robot.y = robot.y.clamp(1, height as u8 - 2);
+        }
+
+        // Robots pushed onto the same tile by shrinking the world are removed
+        let mut positions = vec![];
+        variant.robots.retain(|robot| {
+            let duplicate = positions.contains(&(robot.x, robot.y));
+            positions.push((robot.x, robot.y));
+
+            !duplicate
+        });
+    }
+
+    /// Checks that every variant can be solved and sets the command par to the optimal solution,
+    /// which is saved with the level
+    fn check(&mut self) {
+        match self.level.solve() {
+            Ok(Some(optimal)) => {
+                self.level.optimal_commands = Some(optimal);
+                self.level.par.max_commands = optimal;
+                self.set_status(format!("Solvable in {} commands", optimal), Color::GREEN);
+            }
+            Ok(None) => {
+                self.level.optimal_commands = None;
+                self.set_status("Too large to find the best solution", Color::YELLOW);
+            }
+            Err(error) => self.set_status(error, Color::RED),
+        }
+    }
+
+    fn save(&mut self) {
+        let path = level_path(&self.level.id);
+
+        match self.level.save(&path) {
+            Ok(()) => self.set_status(format!("Saved to {}", path), Color::GREEN),
+            Err(error) => self.set_status(error, Color::RED),
+        }
+    }
+
+    /// Goes back to the saved version of the level
+    fn load(&mut self) {
+        let path = level_path(&self.level.id);
+
+        match Level::load(&path) {
+            Ok(level) => {
+                self.level = level;
+                self.variant_index = 0;
+                self.set_status(format!("Loaded {}", path), Color::GREEN);
+            }
+            Err(error) => self.set_status(error, Color::RED),
+        }
+    }
+}
+
+/// The file a level is saved to, named after its id
+fn level_path(id: &str) -> String {
+    resource_path(&format!("{}/{}.level", LEVELS_DIRECTORY, id))
+}
+
+impl Screen for EditorScreen {
+    fn initialize(&mut self, _screen_width: f32, _screen_height: f32, _assets: &Assets) {}
+
+    fn update(&mut self, d: &mut RaylibDrawHandle, assets: &Assets) -> bool {
+        let brush_keys = [
+            KeyboardKey::KEY_ONE,
+            KeyboardKey::KEY_TWO,
+            KeyboardKey::KEY_THREE,
+            KeyboardKey::KEY_FOUR,
+            KeyboardKey::KEY_FIVE,
+            KeyboardKey::KEY_SIX,
+            KeyboardKey::KEY_SEVEN,
+            KeyboardKey::KEY_EIGHT,
+            KeyboardKey::KEY_NINE,
+        ];
+
+        for (key, brush) in brush_keys.into_iter().zip(Brush::ALL) {
+            if d.is_key_pressed(key) {
+                self.brush = brush;
+            }
+        }
+
+        let wheel = d.get_mouse_wheel_move();
+        if wheel != 0.0 {
+            self.cycle_brush(match wheel > 0.0 {
+                true => -1,
+                false => 1,
+            });
+        }
+
+        let resizes = [
+            (KeyboardKey::KEY_RIGHT, (1, 0)),
+            (KeyboardKey::KEY_LEFT, (-1, 0)),
+            (KeyboardKey::KEY_DOWN, (0, 1)),
+            (KeyboardKey::KEY_UP, (0, -1)),
+        ];
+
+        for (key, (width_change, height_change)) in resizes {
+            if d.is_key_pressed(key) {
+                self.resize(width_change, height_change);
+            }
+        }
+
+        let (tile_x, tile_y) = screen_to_tile_pos(d.get_mouse_x(), d.get_mouse_y());
+        let world = &self.level.variants[self.variant_index].world;
+        let hovered_tile = match (0..world.width as i32).contains(&tile_x)
+            && (0..world.height as i32).contains(&tile_y)
+        {
+            true => Some((tile_x as u8, tile_y as u8)),
+            false => None,
+        };
+
+        // Rotates the hovered robot or mover, or the first robot if there is none under the mouse
+        if d.is_key_pressed(KeyboardKey::KEY_R) {
+            let variant = self.variant();
+            let mover = variant
+                .world
+                .movers
+                .iter_mut()
+                .find(|mover| Some((mover.x, mover.y)) == hovered_tile);
+
+            match mover {
+                Some(mover) => mover.facing = mover.facing.clockwise(),
+                None => {
+                    let index = variant
+                        .robots
+                        .iter()
+                        .position(|robot| Some((robot.x, robot.y)) == hovered_tile)
+                        .unwrap_or(0);
+
+                    variant.robots[index].right();
+                }
+            }
+        }
+
+        if d.is_key_pressed(KeyboardKey::KEY_B) {
+            self.cycle_battery();
+        }
+
+        if d.is_key_pressed(KeyboardKey::KEY_O) {
+            self.cycle_objectives();
+        }
+
+        if d.is_key_pressed(KeyboardKey::KEY_C) {
+            self.check();
+        }
+
+        if d.is_key_pressed(KeyboardKey::KEY_S) {
+            self.save();
+        }
+
+        if d.is_key_pressed(KeyboardKey::KEY_L) {
+            self.load();
+        }
+
+        if d.is_key_pressed(KeyboardKey::KEY_TAB) {
+            self.variant_index = (self.variant_index + 1) % self.level.variants.len();
+        }
+
+        // New variants start as a copy of the current one
+        if d.is_key_pressed(KeyboardKey::KEY_N) {
+            let variant = self.variant().clone();
+            self.level.variants.push(variant);
+            self.variant_index = self.level.variants.len() - 1;
+        }
+
+        if d.is_key_pressed(KeyboardKey::KEY_ENTER) {
+            self.playing = true;
+            return true;
+        }
+
+        if d.is_key_pressed(KeyboardKey::KEY_BACKSPACE) {
+            return true;
+        }
+
+        if let Some(position) = hovered_tile {
+            if d.is_mouse_button_down(MouseButton::MOUSE_BUTTON_LEFT) {
+                self.paint(position, self.brush);
+            } else if d.is_mouse_button_down(MouseButton::MOUSE_BUTTON_RIGHT) {
+                self.paint(position, Brush::Ground);
+            }
+        }
+
+        d.clear_background(Color::BLACK);
+
+        let variant = &self.level.variants[self.variant_index];
+        let tileset = self
+            .level
+            .tileset
+            .as_deref()
+            .and_then(|name| assets.tileset(name));
+        variant.world.draw_themed(d, assets, tileset);
+        for robot in &variant.robots {
+            robot.draw(tile_to_screen_pos_centered(robot.x, robot.y), d, assets);
+        }
+
+        if let Some(position) = hovered_tile {
+            let screen_pos = tile_to_screen_pos(position.0, position.1);
+            let cell_size = tile_to_screen_pos(1, 1).0;
+            d.draw_rectangle_lines(
+                screen_pos.0,
+                screen_pos.1,
+                cell_size,
+                cell_size,
+                Color::YELLOW,
+            );
+        }
+
+        d.draw_text(
+            &format!(
+                "{} {}/{}",
+                self.brush.name(),
+                self.variant_index + 1,
+                self.level.variants.len()
+            ),
+            4,
+            4,
+            24,
+            Color::WHITE,
+        );
+        d.draw_text(
+            "[R]otate [O]bjectives [C]heck [S]ave [L]oad [Backspace] Back",
+            4,
+            d.get_screen_height() - 44,
+            16,
+            Color::WHITE,
+        );
+        d.draw_text(
+            &self.status,
+            4,
+            d.get_screen_height() - 22,
+            16,
+            self.status_color,
+        );
+
+        false
+    }
+
+    fn get_new_screen(&self) -> Box<dyn Screen> {
+        match self.playing {
+            true => Box::new(GameScreen::from_editor(
+                self.level.clone(),
+                &self.script_path,
+            )),
+            false => Box::new(MenuScreen::with_script(self.script_path.clone())),
+        }
+    }
 }
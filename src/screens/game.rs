@@ -1,93 +1,657 @@
-use std::{collections::HashMap, fs};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
 
 use raylib::prelude::*;
 
 use crate::{
-    app::tile_to_screen_pos_centered,
-    interface::instructions::simulate,
+    assets::Assets,
+    components::{stars::Stars, trail::Trail},
+    interface::instructions::{check_script, Simulation},
+    level::{playback_world, run_level, Level},
+    paths::{numbered_path, resource_path, unused_path, REPLAYS_DIRECTORY},
+    progress::Progress,
+    recording::{draw_playback, export_run},
     rendering::Drawable,
-    robot::{self, Robot, RobotCommand},
+    replay::Replay,
+    robot::{RobotCommand, RobotError, RobotResponse},
+    scoring::Score,
+    team::{Team, Trace},
     world::World,
 };
 
-use super::Screen;
+use super::{editor::EditorScreen, menu::MenuScreen, Screen};
+
+const CONSOLE_LINES: usize = 6;
+/// The folder the playback is exported to from the game, in the resource directory
+const EXPORTS_DIRECTORY: &str = "exports";
+/// How many frames pass between checks for changes to the scripts
+const RELOAD_INTERVAL: u8 = 60;
 
 pub struct GameScreen {
     paused: bool,
     playback_ended: bool,
     tick: u8,
-    robot: Robot,
+    team: Team,
     world: World,
-    commands: Vec<RobotCommand>,
+    /// The script of every robot, saved with replays
+    scripts: Vec<Vec<u8>>,
+    /// The trace and output recorded for each variant of the level
+    runs: Vec<Simulation>,
+    variant_index: usize,
     command_index: usize,
+    /// Why a robot stopped before reaching an exit
+    failure: Option<RobotError>,
+    trail: Trail,
+    trail_visible: bool,
+    /// Where the last export or replay was saved or why it or saving the progress failed
+    save_status: Option<Result<String, String>>,
+    level: Level,
+    score: Score,
+    progress_saved: bool,
+    /// Levels that are test-played from the editor go back to it instead of the menu
+    from_editor: bool,
+    console_open: bool,
+    /// How many lines the console is scrolled up from the latest one
+    console_scroll: usize,
+    file_path: String,
+    /// Replays keep the scripts they were recorded with instead of reloading the files
+    replay: bool,
+    /// When the scripts were last changed, they are run again once this changes
+    scripts_modified: Option<SystemTime>,
+    reload_timer: u8,
+    /// Why the scripts could not be loaded
+    load_error: Option<String>,
+    /// The file every robot runs, empty if there is only one robot or the scripts come from a
+    /// replay
+    script_names: Vec<String>,
+}
+
+fn robot_count(level: &Level) -> usize {
+    level
+        .variants
+        .iter()
+        .map(|variant| variant.robots.len())
+        .max()
+        .unwrap_or(1)
+}
+
+/// The paths of the scripts of the robots, `<name>_<n>.lua` next to the main script for robot `n`.
+/// A single robot always runs the main script, so there are none for levels with one robot
+fn robot_script_paths(file_path: &str, robot_count: usize) -> Vec<PathBuf> {
+    if robot_count <= 1 {
+        return vec![];
+    }
+
+    let path = Path::new(file_path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+
+    (1..=robot_count)
+        .map(|id| path.with_file_name(format!("{}_{}.lua", stem, id)))
+        .collect()
+}
+
+/// The name of the file every robot runs, for levels with more than one robot
+fn robot_script_names(file_path: &str, robot_count: usize) -> Vec<String> {
+    robot_script_paths(file_path, robot_count)
+        .into_iter()
+        .map(|path| match path.is_file() {
+            true => path,
+            false => PathBuf::from(file_path),
+        })
+        .map(|path| {
+            path.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect()
+}
+
+/// Loads the script for every robot. Robot `n` runs its own script if there is one and the main
+/// script otherwise
+fn load_scripts(file_path: &str, robot_count: usize) -> Result<Vec<Vec<u8>>, String> {
+    let main_script =
+        fs::read(file_path).map_err(|error| format!("Could not read {}: {}", file_path, error))?;
+
+    let mut scripts: Vec<Vec<u8>> = robot_script_paths(file_path, robot_count)
+        .into_iter()
+        .map(|path| fs::read(path).unwrap_or_else(|_| main_script.clone()))
+        .collect();
+
+    if scripts.is_empty() {
+        scripts.push(main_script);
+    }
+
+    for script in &scripts {
+        check_script(script).map_err(|error| error.to_string())?;
+    }
+
+    Ok(scripts)
+}
+
+/// When the main script or any of the scripts of the robots was last changed
+fn scripts_modified(file_path: &str, robot_count: usize) -> Option<SystemTime> {
+    robot_script_paths(file_path, robot_count)
+        .into_iter()
+        .chain([PathBuf::from(file_path)])
+        .filter_map(|path| {
+            fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        })
+        .max()
+}
+
+/// The error of the first script that did not start in any variant
+fn script_load_error(runs: &[Simulation]) -> Option<String> {
+    runs.iter().find_map(|run| run.load_error.clone())
+}
+
+/// Loads the scripts of the robots. If they cannot be loaded there are no scripts and the error
+/// is returned to be shown instead
+fn try_load_scripts(level: &Level, file_path: &str) -> (Vec<Vec<u8>>, Option<String>) {
+    match load_scripts(file_path, robot_count(level)) {
+        Ok(scripts) => (scripts, None),
+        Err(error) => (vec![], Some(error)),
+    }
 }
 
 impl GameScreen {
-    pub fn new(world: World, robot: Robot, file_path: &str) -> GameScreen {
-        // TODO: Handle this error
-        let file = fs::read(file_path).unwrap();
+    pub fn new(level: Level, file_path: &str) -> GameScreen {
+        let (scripts, load_error) = try_load_scripts(&level, file_path);
 
-        let lua = mlua::Lua::new();
-        let commands = simulate(&lua, robot.clone(), world.clone(), file).unwrap();
+        Self::with_scripts(level, file_path, scripts, load_error)
+    }
+
+    pub fn from_editor(level: Level, file_path: &str) -> GameScreen {
+        Self {
+            from_editor: true,
+            ..Self::new(level, file_path)
+        }
+    }
+
+    /// Plays a replay back with the scripts it was recorded with and the runs `Replay::verify`
+    /// got from them
+    pub fn from_replay(
+        level: Level,
+        replay: &Replay,
+        runs: Vec<Simulation>,
+        score: Score,
+        file_path: &str,
+    ) -> GameScreen {
+        let scripts = replay.script_sources();
+
+        Self {
+            replay: true,
+            script_names: vec![],
+            ..Self::with_runs(level, file_path, scripts, (runs, score), None)
+        }
+    }
+
+    fn with_scripts(
+        level: Level,
+        file_path: &str,
+        scripts: Vec<Vec<u8>>,
+        load_error: Option<String>,
+    ) -> GameScreen {
+        let runs = run_level(&level, &scripts);
+
+        Self::with_runs(level, file_path, scripts, runs, load_error)
+    }
+
+    fn with_runs(
+        level: Level,
+        file_path: &str,
+        scripts: Vec<Vec<u8>>,
+        (runs, score): (Vec<Simulation>, Score),
+        load_error: Option<String>,
+    ) -> GameScreen {
+        let scripts_modified = scripts_modified(file_path, robot_count(&level));
+        let script_names = robot_script_names(file_path, robot_count(&level));
+        let load_error = load_error.or_else(|| script_load_error(&runs));
 
         Self {
             paused: true,
             playback_ended: false,
             command_index: 0,
+            failure: None,
+            trail: Trail::new(&level.variants[0].robots),
+            trail_visible: true,
+            save_status: None,
             tick: 0,
-            robot,
-            world,
-            commands,
+            team: Team::new(
+                level.variants[0].robots.clone(),
+                level.objectives.clone(),
+                &level.variants[0].world,
+            ),
+            world: playback_world(&level, 0),
+            scripts,
+            runs,
+            variant_index: 0,
+            level,
+            score,
+            progress_saved: false,
+            from_editor: false,
+            console_open: false,
+            console_scroll: 0,
+            file_path: file_path.to_string(),
+            replay: false,
+            scripts_modified,
+            reload_timer: 0,
+            load_error,
+            script_names,
+        }
+    }
+
+    fn select_variant(&mut self, variant_index: usize) {
+        let variant = &self.level.variants[variant_index];
+
+        self.variant_index = variant_index;
+        self.team = Team::new(
+            variant.robots.clone(),
+            self.level.objectives.clone(),
+            &variant.world,
+        );
+        self.world = playback_world(&self.level, variant_index);
+        self.command_index = 0;
+        self.failure = None;
+        self.trail = Trail::new(&variant.robots);
+        self.tick = 0;
+        self.paused = true;
+        self.playback_ended = false;
+        self.console_scroll = 0;
+    }
+
+    /// Runs the changed scripts again and restarts the playback of the current variant
+    fn reload(&mut self) {
+        let (scripts, load_error) = try_load_scripts(&self.level, &self.file_path);
+        let (runs, score) = run_level(&self.level, &scripts);
+        let load_error = load_error.or_else(|| script_load_error(&runs));
+
+        self.scripts = scripts;
+        self.runs = runs;
+        self.score = score;
+        self.load_error = load_error;
+        self.script_names = robot_script_names(&self.file_path, robot_count(&self.level));
+        self.progress_saved = false;
+        self.select_variant(self.variant_index);
+    }
+
+    /// Saves the playback of every variant as an animated GIF or numbered PNG files, see
+    /// `export_run`. Levels with more than one variant get a file for each, numbered like
+    /// `run-2.gif`
+    ///
+    /// Returns where the variants were saved
+    pub fn export(
+        &self,
+        d: &mut RaylibDrawHandle,
+        assets: &Assets,
+        path: &Path,
+    ) -> Result<Vec<PathBuf>, String> {
+        if let Some(error) = &self.load_error {
+            return Err(error.clone());
+        }
+
+        let mut paths = vec![];
+
+        for (variant_index, run) in self.runs.iter().enumerate() {
+            let path = match self.runs.len() {
+                1 => path.to_path_buf(),
+                _ => numbered_path(path, variant_index + 1),
+            };
+
+            export_run(d, assets, &self.level, variant_index, &run.trace, &path)?;
+            paths.push(path);
+        }
+
+        Ok(paths)
+    }
+
+    /// Saves the scripts with the trace of every variant and the stars they earned as a replay
+    pub fn save_replay(&self, path: &Path) -> Result<(), String> {
+        if let Some(error) = &self.load_error {
+            return Err(error.clone());
+        }
+
+        let traces: Vec<Trace> = self.runs.iter().map(|run| run.trace.clone()).collect();
+
+        Replay::record(&self.level, &self.scripts, traces, self.score.stars)
+            .save(path)
+            .map_err(|error| format!("Error writing {}: {}", path.display(), error))
+    }
+
+    fn save_progress(&mut self) {
+        self.progress_saved = true;
+
+        let mut progress = Progress::load();
+        if progress.record(&self.level.id, self.score.stars) {
+            if let Err(error) = progress.save() {
+                self.save_status = Some(Err(format!("Could not save the progress: {}", error)));
+            }
+        }
+    }
+
+    fn draw_objectives(&self, d: &mut RaylibDrawHandle) {
+        let height = d.get_screen_height();
+        let count = self.level.objectives.len() as i32;
+
+        for (i, objective) in self.level.objectives.iter().enumerate() {
+            let (done, total) = objective.progress(&self.team.robots, &self.world);
+            d.draw_text(
+                &format!("{} {}/{}", objective.describe(), done, total),
+                4,
+                height - (count - i as i32) * 20,
+                16,
+                match done >= total {
+                    true => Color::GREEN,
+                    false => Color::WHITE,
+                },
+            );
+        }
+    }
+
+    /// Shows why the scripts could not be loaded over the whole screen
+    fn draw_load_error(&self, d: &mut RaylibDrawHandle, error: &str) {
+        let (width, height) = (d.get_screen_width(), d.get_screen_height());
+        d.draw_rectangle(0, 0, width, height, Color::BLACK.alpha(0.85));
+        d.draw_text("Script error", 4, 4, 24, Color::RED);
+
+        // Wraps the message at the edge of the screen
+        let mut lines: Vec<String> = vec![];
+        for paragraph in error.lines() {
+            let mut line = String::new();
+
+            for word in paragraph.split_whitespace() {
+                let candidate = match line.is_empty() {
+                    true => word.to_string(),
+                    false => format!("{} {}", line, word),
+                };
+
+                if !line.is_empty() && d.measure_text(&candidate, 16) > width - 8 {
+                    lines.push(line);
+                    line = word.to_string();
+                } else {
+                    line = candidate;
+                }
+            }
+
+            lines.push(line);
+        }
+
+        for (i, line) in lines.iter().enumerate() {
+            d.draw_text(line, 4, 36 + i as i32 * 18, 16, Color::WHITE);
+        }
+
+        d.draw_text(
+            "Save the script to try again",
+            4,
+            height - 22,
+            16,
+            Color::GRAY,
+        );
+    }
+
+    /// Shows the output of the scripts up to the command that is being played back
+    fn draw_console(&self, d: &mut RaylibDrawHandle) {
+        let (width, height) = (d.get_screen_width(), d.get_screen_height());
+        let panel_y = height - CONSOLE_LINES as i32 * 16 - 8;
+
+        d.draw_rectangle(0, panel_y, width, height - panel_y, Color::BLACK.alpha(0.8));
+
+        let lines = self.revealed_output();
+        let end = lines.len() - self.console_scroll.min(lines.len());
+        let start = end.saturating_sub(CONSOLE_LINES);
+
+        for (i, (_, line)) in lines[start..end].iter().enumerate() {
+            d.draw_text(line, 4, panel_y + 4 + i as i32 * 16, 14, Color::LIGHTGRAY);
+        }
+    }
+
+    /// The lines the scripts printed before the command that is played back next
+    fn revealed_output(&self) -> &[(usize, String)] {
+        let output = &self.runs[self.variant_index].output;
+        let count = output
+            .iter()
+            .take_while(|(command_index, _)| *command_index <= self.command_index)
+            .count();
+
+        &output[..count]
+    }
+
+    fn draw_results(&self, d: &mut RaylibDrawHandle, assets: &Assets) {
+        let (width, height) = (d.get_screen_width(), d.get_screen_height());
+        let panel_height = 112 + self.score.unmet_requirements.len() as i32 * 20;
+        let panel_y = height - panel_height;
+
+        d.draw_rectangle(0, panel_y, width, panel_height, Color::BLACK.alpha(0.8));
+
+        let stars = Stars::new(self.score.stars, 14.0);
+        stars.draw(
+            (width / 2 - stars.width() as i32 / 2, panel_y + 8),
+            d,
+            assets,
+        );
+
+        let par = self.level.par;
+        let lines = [
+            match self.score.optimal_commands {
+                Some(optimal) => format!(
+                    "Commands {}/{} (best {})",
+                    self.score.commands, par.max_commands, optimal
+                ),
+                None => format!("Commands {}/{}", self.score.commands, par.max_commands),
+            },
+            format!(
+                "Lines {}/{}  Scans {}/{}",
+                self.score.lines, par.max_lines, self.score.scans, par.max_scans
+            ),
+        ];
+
+        for (i, line) in lines.iter().enumerate() {
+            let text_width = d.measure_text(line, 20);
+            d.draw_text(
+                line,
+                width / 2 - text_width / 2,
+                panel_y + 44 + i as i32 * 24,
+                20,
+                match self.score.completed {
+                    true => Color::WHITE,
+                    false => Color::GRAY,
+                },
+            );
+        }
+
+        for (i, requirement) in self.score.unmet_requirements.iter().enumerate() {
+            let text = requirement.describe();
+            let text_width = d.measure_text(&text, 16);
+            d.draw_text(
+                &text,
+                width / 2 - text_width / 2,
+                panel_y + 96 + i as i32 * 20,
+                16,
+                Color::RED,
+            );
         }
     }
 }
+
 impl Screen for GameScreen {
-    fn initialize(&mut self, _screen_width: f32, _screen_height: f32, _textures: &HashMap<String, Texture2D>, _fonts: &HashMap<String, Font>) {
-    }
+    fn initialize(&mut self, _screen_width: f32, _screen_height: f32, _assets: &Assets) {}
 
-    fn update(&mut self, d: &mut RaylibDrawHandle, textures: &HashMap<String, Texture2D>, fonts: &HashMap<String, Font>) -> bool {
+    fn update(&mut self, d: &mut RaylibDrawHandle, assets: &Assets) -> bool {
         if d.is_key_pressed(KeyboardKey::KEY_SPACE) {
             self.paused = !self.paused;
         }
 
+        if d.is_key_pressed(KeyboardKey::KEY_BACKSPACE) {
+            return true;
+        }
+
+        if d.is_key_pressed(KeyboardKey::KEY_TAB) {
+            self.select_variant((self.variant_index + 1) % self.level.variants.len());
+        }
+
+        if d.is_key_pressed(KeyboardKey::KEY_C) {
+            self.console_open = !self.console_open;
+        }
+
+        if d.is_key_pressed(KeyboardKey::KEY_T) {
+            self.trail_visible = !self.trail_visible;
+        }
+
+        if d.is_key_pressed(KeyboardKey::KEY_E) && self.playback_ended {
+            let path = resource_path(&format!("{}/{}.gif", EXPORTS_DIRECTORY, self.level.id));
+
+            self.save_status = Some(match self.export(d, assets, Path::new(&path)) {
+                Ok(_) => Ok(format!("Saved to ./{}", EXPORTS_DIRECTORY)),
+                Err(error) => Err(error),
+            });
+        }
+
+        if d.is_key_pressed(KeyboardKey::KEY_S) && self.playback_ended {
+            let path = resource_path(&format!("{}/{}.replay", REPLAYS_DIRECTORY, self.level.id));
+            let path = unused_path(Path::new(&path));
+
+            self.save_status = Some(match self.save_replay(&path) {
+                Ok(()) => Ok(format!("Saved to {}", path.display())),
+                Err(error) => Err(error),
+            });
+        }
+
+        self.reload_timer = (self.reload_timer + 1) % RELOAD_INTERVAL;
+        if self.reload_timer == 0 && !self.replay {
+            let modified = scripts_modified(&self.file_path, robot_count(&self.level));
+
+            if modified != self.scripts_modified {
+                self.scripts_modified = modified;
+                self.reload();
+            }
+        }
+
+        // Scrolling up shows older lines
+        let wheel = d.get_mouse_wheel_move();
+        if self.console_open && wheel != 0.0 {
+            let max_scroll = self.revealed_output().len().saturating_sub(CONSOLE_LINES);
+            self.console_scroll = match wheel > 0.0 {
+                true => (self.console_scroll + 1).min(max_scroll),
+                false => self.console_scroll.saturating_sub(1),
+            };
+        }
+
         d.clear_background(Color::BLACK);
 
         if !self.paused {
             self.tick = (self.tick + 1) % 30;
 
-            if !self.playback_ended && self.commands.len() > self.command_index && self.tick == 0 {
-                self.robot.scanning = false;
-                match self.commands[self.command_index] {
-                    RobotCommand::Forward => match self.robot.forward(&self.world) {
-                        Ok(()) => (),
-                        Err(_) => {
+            let trace = &self.runs[self.variant_index].trace;
+
+            if !self.playback_ended && trace.len() > self.command_index && self.tick == 0 {
+                match trace[self.command_index] {
+                    (_, RobotCommand::End) => self.playback_ended = true,
+                    (index, command) => {
+                        let response = self.team.execute(index, command, &mut self.world);
+                        self.trail
+                            .record(index, command, &self.team.robots[index], &response);
+
+                        if let RobotResponse::Error(error) = response {
+                            self.failure = Some(error);
                             self.playback_ended = true;
                         }
-                    },
-                    RobotCommand::Left => self.robot.left(),
-                    RobotCommand::Right => self.robot.right(),
-                    RobotCommand::Scan => self.robot.scanning = true,
-                    RobotCommand::End => self.playback_ended = true,
+                    }
                 };
 
                 self.command_index += 1;
 
-                if self.robot.is_on_end_tile(&self.world) {
+                if self.team.is_won() || self.command_index >= trace.len() {
                     self.playback_ended = true;
                 }
             }
         }
 
-        self.world.draw((0, 0), d, &textures, &fonts);
-        self.robot.draw(
-            tile_to_screen_pos_centered(self.robot.x, self.robot.y),
+        draw_playback(
             d,
-            textures,
-            fonts,
+            assets,
+            &self.level,
+            &self.world,
+            &self.team.robots,
+            match self.trail_visible {
+                true => Some(&self.trail),
+                false => None,
+            },
         );
 
+        if self.level.variants.len() > 1 {
+            let text = format!(
+                "[Tab] {}/{}",
+                self.variant_index + 1,
+                self.level.variants.len()
+            );
+            let text_width = d.measure_text(&text, 24);
+            d.draw_text(
+                &text,
+                d.get_screen_width() - text_width - 4,
+                4,
+                24,
+                match self.score.solved_variants[self.variant_index] {
+                    true => Color::GREEN,
+                    false => Color::RED,
+                },
+            );
+        }
+
+        if !self.playback_ended {
+            self.draw_objectives(d);
+        }
+
+        let batteries = self.team.robots.iter().filter_map(|robot| robot.battery);
+        for (i, battery) in batteries.enumerate() {
+            let text = format!("Battery {}/{}", battery.charge, battery.capacity);
+            let text_width = d.measure_text(&text, 16);
+            d.draw_text(
+                &text,
+                d.get_screen_width() - text_width - 4,
+                32 + i as i32 * 20,
+                16,
+                match battery.charge {
+                    0 => Color::RED,
+                    _ => Color::WHITE,
+                },
+            );
+        }
+
         if self.playback_ended {
+            // Replays can be someone else's run, and every practice level is generated anew, so
+            // neither counts as progress
+            if !self.progress_saved && !self.replay && !self.level.is_practice() {
+                self.save_progress();
+            }
+
+            self.draw_results(d, assets);
             d.draw_text("[Escape] End", 4, 4, 24, Color::WHITE);
+            d.draw_text("[Backspace] Back", 4, 32, 16, Color::WHITE);
+
+            // Scripts that stopped with an error are shown when no robot failed before
+            let error = self.runs[self.variant_index].error.as_deref();
+            match (self.failure, error) {
+                (Some(failure), _) => d.draw_text(&failure.to_string(), 4, 52, 16, Color::RED),
+                (None, Some(error)) => d.draw_text(error, 4, 52, 16, Color::RED),
+                (None, None) => {}
+            }
+
+            d.draw_text("[T] Trail [E] Export [S] Replay", 4, 72, 16, Color::WHITE);
+
+            if !self.runs[self.variant_index].output.is_empty() {
+                d.draw_text("[C] Console", 4, 92, 16, Color::WHITE);
+            }
+
+            match &self.save_status {
+                Some(Ok(message)) => d.draw_text(message, 4, 112, 16, Color::GREEN),
+                Some(Err(error)) => d.draw_text(error, 4, 112, 16, Color::RED),
+                None => {}
+            }
         } else {
             d.draw_text(
                 match self.paused {
@@ -102,17 +666,37 @@ impl Screen for GameScreen {
                     false => Color::GREEN,
                 },
             );
+
+            d.draw_text("[T] Trail", 4, 32, 16, Color::WHITE);
+
+            if !self.runs[self.variant_index].output.is_empty() {
+                d.draw_text("[C] Console", 4, 52, 16, Color::WHITE);
+            }
+
+            for (i, name) in self.script_names.iter().enumerate() {
+                let text = format!("{}: {}", i + 1, name);
+                d.draw_text(&text, 4, 76 + i as i32 * 20, 16, Color::LIGHTGRAY);
+            }
+        }
+
+        if self.console_open {
+            self.draw_console(d);
+        }
+
+        if let Some(error) = &self.load_error {
+            self.draw_load_error(d, error);
         }
 
         false
     }
 
-    fn get_new_screen(&self) -> Box<dyn Screen>
-    {
-        Box::new(GameScreen::new(
-            World::new((0, 0), (1, 1)),
-            Robot::new(0, 0, robot::Direction::Right),
-            "",
-        ))
+    fn get_new_screen(&self) -> Box<dyn Screen> {
+        match self.from_editor {
+            true => Box::new(EditorScreen::with_level(
+                self.level.clone(),
+                self.file_path.clone(),
+            )),
+            false => Box::new(MenuScreen::with_script(self.file_path.clone())),
+        }
     }
 }
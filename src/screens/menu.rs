@@ -1,26 +1,70 @@
-use std::collections::HashMap;
+use std::{
+    path::Path,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use raylib::prelude::*;
 
-use crate::{components::button::Button, rendering::Drawable, robot::Robot, world::World};
+use crate::{
+    assets::{Assets, FontKey},
+    components::button::Button,
+    level::{default_level, practice_level, Level},
+    paths::{resource_path, DEFAULT_SCRIPT},
+    rendering::Drawable,
+    world::generator::MazeStyle,
+};
 
-use super::{game::GameScreen, Screen};
+use super::{
+    editor::EditorScreen, game::GameScreen, replays::ReplaysScreen, scripts::ScriptsScreen, Screen,
+};
+
+#[derive(Default)]
+enum MenuItem {
+    #[default]
+    Play,
+    Practice,
+    Editor,
+    Scripts,
+    Replays,
+}
 
 #[derive(Default)]
 pub struct MenuScreen {
     width: f32,
     height: f32,
     play_button: Button,
+    practice_button: Button,
+    editor_button: Button,
+    scripts_button: Button,
+    replays_button: Button,
+    selected: MenuItem,
+    /// The script the robots run in every level started from the menu
+    script_path: String,
+    /// The kind of maze practice levels are generated as
+    practice_style: MazeStyle,
+    /// The practice level that is being generated off the frame loop, finding its best
+    /// solution can take a while
+    practice_generation: Option<Receiver<Level>>,
+    practice_level: Option<Level>,
 }
 
 impl MenuScreen {
     pub fn new() -> Self {
-        Self::default()
+        Self::with_script(resource_path(DEFAULT_SCRIPT))
+    }
+
+    pub fn with_script(script_path: String) -> Self {
+        Self {
+            script_path,
+            ..Self::default()
+        }
     }
 }
 
 impl Screen for MenuScreen {
-    fn initialize(&mut self, screen_width: f32, screen_height: f32, _textures: &HashMap<String, Texture2D>, fonts: &HashMap<String, raylib::text::Font>) {
+    fn initialize(&mut self, screen_width: f32, screen_height: f32, assets: &Assets) {
         self.width = screen_width;
         self.height = screen_height;
 
@@ -39,45 +83,190 @@ impl Screen for MenuScreen {
             Color::WHITE,
             Color::DARKGRAY,
             Color::GRAY,
-            fonts.get("geist").unwrap(),
+            assets.font(FontKey::Geist),
+            24.0,
+        );
+
+        self.practice_button = Button::new(
+            Rectangle::new(
+                self.width / 2.0 - button_width / 2.0,
+                96.0,
+                button_width,
+                40.0,
+            ),
+            "Practice",
+            Color::WHITESMOKE,
+            Color::WHITE,
+            Color::DARKGRAY,
+            Color::GRAY,
+            assets.font(FontKey::Geist),
+            24.0,
+        );
+
+        self.editor_button = Button::new(
+            Rectangle::new(
+                self.width / 2.0 - button_width / 2.0,
+                152.0,
+                button_width,
+                40.0,
+            ),
+            "Editor",
+            Color::WHITESMOKE,
+            Color::WHITE,
+            Color::DARKGRAY,
+            Color::GRAY,
+            assets.font(FontKey::Geist),
+            24.0,
+        );
+
+        self.scripts_button = Button::new(
+            Rectangle::new(
+                self.width / 2.0 - button_width / 2.0,
+                208.0,
+                button_width,
+                40.0,
+            ),
+            "Scripts",
+            Color::WHITESMOKE,
+            Color::WHITE,
+            Color::DARKGRAY,
+            Color::GRAY,
+            assets.font(FontKey::Geist),
+            24.0,
+        );
+
+        self.replays_button = Button::new(
+            Rectangle::new(
+                self.width / 2.0 - button_width / 2.0,
+                264.0,
+                button_width,
+                40.0,
+            ),
+            "Replays",
+            Color::WHITESMOKE,
+            Color::WHITE,
+            Color::DARKGRAY,
+            Color::GRAY,
+            assets.font(FontKey::Geist),
             24.0,
         );
     }
 
-    fn update(&mut self, d: &mut RaylibDrawHandle, textures: &HashMap<String, Texture2D>, fonts: &HashMap<String, raylib::text::Font>) -> bool {
+    fn update(&mut self, d: &mut RaylibDrawHandle, assets: &Assets) -> bool {
         d.clear_background(Color::BLACK);
 
         let mouse_clicked = d.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT);
 
-        self.play_button.draw((0, 0), d, &textures, fonts);
+        if let Some(receiver) = &self.practice_generation {
+            match receiver.try_recv() {
+                Ok(level) => {
+                    self.practice_generation = None;
+                    self.practice_level = Some(level);
+                    self.selected = MenuItem::Practice;
+                    return true;
+                }
+                Err(TryRecvError::Disconnected) => self.practice_generation = None,
+                Err(TryRecvError::Empty) => {}
+            }
+        }
+
+        if d.is_key_pressed(KeyboardKey::KEY_M) {
+            let index = MazeStyle::ALL
+                .iter()
+                .position(|style| *style == self.practice_style)
+                .unwrap_or(0);
+
+            self.practice_style = MazeStyle::ALL[(index + 1) % MazeStyle::ALL.len()];
+        }
+
+        self.play_button.draw((0, 0), d, assets);
         if mouse_clicked && self.play_button.is_hovered(&d) {
             return true;
         }
 
-        false
-    }
+        self.practice_button.draw((0, 0), d, assets);
+        if mouse_clicked && self.practice_button.is_hovered(d) && self.practice_generation.is_none()
+        {
+            let seed = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_nanos() as u64)
+                .unwrap_or(0);
+            let style = self.practice_style;
+            let (tx, rx) = mpsc::channel();
 
-    fn get_new_screen(&self) -> Box<dyn Screen> {
-        let mut world = World::new((11, 11), (10, 5));
+            thread::spawn(move || {
+                let _ = tx.send(practice_level(seed, style));
+            });
+            self.practice_generation = Some(rx);
+        }
+
+        self.editor_button.draw((0, 0), d, assets);
+        if mouse_clicked && self.editor_button.is_hovered(d) {
+            self.selected = MenuItem::Editor;
+            return true;
+        }
 
-        for x in 0..world.width {
-            world.set_tile((x, 0), crate::world::Tile::Wall);
-            world.set_tile((x, world.height - 1), crate::world::Tile::Wall);
+        self.scripts_button.draw((0, 0), d, assets);
+        if mouse_clicked && self.scripts_button.is_hovered(d) {
+            self.selected = MenuItem::Scripts;
+            return true;
+        }
+
+        self.replays_button.draw((0, 0), d, assets);
+        if mouse_clicked && self.replays_button.is_hovered(d) {
+            self.selected = MenuItem::Replays;
+            return true;
         }
 
-        for y in 0..world.height {
-            world.set_tile((0, y), crate::world::Tile::Wall);
-            world.set_tile((world.width - 1, y), crate::world::Tile::Wall);
+        let script_name = Path::new(&self.script_path)
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let text = format!("Script: {}", script_name);
+        let text_width = d.measure_text(&text, 16);
+        d.draw_text(
+            &text,
+            self.width as i32 / 2 - text_width / 2,
+            320,
+            16,
+            Color::GRAY,
+        );
+
+        let text = format!("[M] Practice maze: {}", self.practice_style.name());
+        let text_width = d.measure_text(&text, 16);
+        d.draw_text(
+            &text,
+            self.width as i32 / 2 - text_width / 2,
+            336,
+            16,
+            Color::GRAY,
+        );
+
+        if self.practice_generation.is_some() {
+            let text = "Generating the practice level...";
+            let text_width = d.measure_text(text, 16);
+            d.draw_text(
+                text,
+                self.width as i32 / 2 - text_width / 2,
+                352,
+                16,
+                Color::WHITE,
+            );
         }
 
-        let robot = Robot::new(1, 5, crate::robot::Direction::Right);
+        false
+    }
 
-        world.set_tile((5, 5), crate::world::Tile::Wall);
-        world.set_tile((7, 4), crate::world::Tile::Wall);
-        world.set_tile((5, 6), crate::world::Tile::Wall);
-        world.set_tile((6, 7), crate::world::Tile::Wall);
-        world.set_tile((3, 2), crate::world::Tile::Wall);
+    fn get_new_screen(&self) -> Box<dyn Screen> {
+        let level = match self.selected {
+            MenuItem::Play => default_level(),
+            MenuItem::Practice => self.practice_level.clone().unwrap_or_else(default_level),
+            MenuItem::Editor => return Box::new(EditorScreen::new(self.script_path.clone())),
+            MenuItem::Scripts => return Box::new(ScriptsScreen::new(self.script_path.clone())),
+            MenuItem::Replays => return Box::new(ReplaysScreen::new(self.script_path.clone())),
+        };
 
-        Box::new(GameScreen::new(world, robot, "./lua/test.lua"))
+        Box::new(GameScreen::new(level, &self.script_path))
     }
 }
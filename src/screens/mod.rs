@@ -1,14 +1,16 @@
-use std::collections::HashMap;
+use raylib::drawing::RaylibDrawHandle;
 
-use raylib::{drawing::RaylibDrawHandle, text::Font, texture::Texture2D};
+use crate::assets::Assets;
 
 pub mod editor;
 pub mod game;
 pub mod menu;
+pub mod replays;
+pub mod scripts;
 
 pub trait Screen {
-    fn initialize(&mut self, screen_width: f32, screen_height: f32, textures: &HashMap<String, Texture2D>, fonts: &HashMap<String, Font>);
+    fn initialize(&mut self, screen_width: f32, screen_height: f32, assets: &Assets);
     // Returns whether the screen wants to end
-    fn update(&mut self, d: &mut RaylibDrawHandle, textures: &HashMap<String, Texture2D>, fonts: &HashMap<String, raylib::text::Font>) -> bool;
+    fn update(&mut self, d: &mut RaylibDrawHandle, assets: &Assets) -> bool;
     fn get_new_screen(&self) -> Box<dyn Screen>;
 }
@@ -0,0 +1,198 @@
+use std::{fs, path::Path};
+
+use raylib::prelude::*;
+
+use crate::{
+    assets::Assets,
+    interface::instructions::Simulation,
+    level::Level,
+    paths::{resource_path, REPLAYS_DIRECTORY},
+    replay::{find_level, Replay, ReplayError},
+    scoring::Score,
+};
+
+use super::{game::GameScreen, menu::MenuScreen, Screen};
+
+const VISIBLE_FILES: usize = 11;
+
+/// A replay that was checked against its level, with the runs of its scripts
+struct PickedReplay {
+    level: Level,
+    replay: Replay,
+    runs: Vec<Simulation>,
+    score: Score,
+}
+
+/// Loads a replay with the level it was recorded on and checks that it was not changed
+fn load_replay(path: &str) -> Result<PickedReplay, ReplayError> {
+    let replay = Replay::load(path)?;
+    let level = find_level(&replay.level_id)
+        .ok_or_else(|| ReplayError::UnknownLevel(replay.level_id.clone()))?;
+
+    let (runs, score) = replay.verify(&level)?;
+
+    Ok(PickedReplay {
+        level,
+        replay,
+        runs,
+        score,
+    })
+}
+
+/// Lists the replay files in the replays folder to play one back
+pub struct ReplaysScreen {
+    /// The script that stays selected once the player goes back to the menu
+    script_path: String,
+    files: Vec<String>,
+    selected: usize,
+    picked: Option<PickedReplay>,
+    /// Why the last picked replay cannot be played back
+    error: Option<String>,
+}
+
+impl ReplaysScreen {
+    pub fn new(script_path: String) -> Self {
+        let mut files: Vec<String> = fs::read_dir(resource_path(REPLAYS_DIRECTORY))
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .filter(|path| {
+                        path.extension()
+                            .is_some_and(|extension| extension == "replay")
+                    })
+                    .map(|path| path.to_string_lossy().into_owned())
+                    .collect()
+            })
+            .unwrap_or_default();
+        files.sort();
+
+        Self {
+            script_path,
+            files,
+            selected: 0,
+            picked: None,
+            error: None,
+        }
+    }
+
+    /// Returns whether the selected replay can be played back
+    fn pick(&mut self) -> bool {
+        match load_replay(&self.files[self.selected]) {
+            Ok(picked) => {
+                self.picked = Some(picked);
+                true
+            }
+            Err(error) => {
+                self.error = Some(error.to_string());
+                false
+            }
+        }
+    }
+}
+
+impl Screen for ReplaysScreen {
+    fn initialize(&mut self, _screen_width: f32, _screen_height: f32, _assets: &Assets) {}
+
+    fn update(&mut self, d: &mut RaylibDrawHandle, _assets: &Assets) -> bool {
+        if d.is_key_pressed(KeyboardKey::KEY_BACKSPACE) {
+            return true;
+        }
+
+        if d.is_key_pressed(KeyboardKey::KEY_DOWN) && self.selected + 1 < self.files.len() {
+            self.selected += 1;
+        }
+
+        if d.is_key_pressed(KeyboardKey::KEY_UP) {
+            self.selected = self.selected.saturating_sub(1);
+        }
+
+        // Scrolls just far enough to keep the selected file visible
+        let first = (self.selected + 1).saturating_sub(VISIBLE_FILES);
+
+        let row = (d.get_mouse_y() - 40).div_euclid(24);
+        let hovered = usize::try_from(row)
+            .ok()
+            .map(|row| first + row)
+            .filter(|index| *index < self.files.len() && *index < first + VISIBLE_FILES);
+
+        if let Some(index) = hovered {
+            if d.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
+                self.selected = index;
+
+                if self.pick() {
+                    return true;
+                }
+            }
+        }
+
+        if d.is_key_pressed(KeyboardKey::KEY_ENTER) && !self.files.is_empty() && self.pick() {
+            return true;
+        }
+
+        d.clear_background(Color::BLACK);
+        d.draw_text("Replays", 4, 4, 24, Color::WHITE);
+
+        if self.files.is_empty() {
+            d.draw_text(
+                &format!("No .replay files in {}", resource_path(REPLAYS_DIRECTORY)),
+                4,
+                40,
+                16,
+                Color::GRAY,
+            );
+        }
+
+        for (i, file) in self
+            .files
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(VISIBLE_FILES)
+        {
+            let name = Path::new(file)
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy();
+
+            d.draw_text(
+                &name,
+                4,
+                40 + (i - first) as i32 * 24,
+                20,
+                match (i == self.selected, Some(i) == hovered) {
+                    (true, _) => Color::YELLOW,
+                    (false, true) => Color::WHITE,
+                    (false, false) => Color::GRAY,
+                },
+            );
+        }
+
+        if let Some(error) = &self.error {
+            d.draw_text(error, 4, d.get_screen_height() - 44, 16, Color::RED);
+        }
+
+        d.draw_text(
+            "[Enter] Play [Backspace] Back",
+            4,
+            d.get_screen_height() - 22,
+            16,
+            Color::WHITE,
+        );
+
+        false
+    }
+
+    fn get_new_screen(&self) -> Box<dyn Screen> {
+        match &self.picked {
+            Some(picked) => Box::new(GameScreen::from_replay(
+                picked.level.clone(),
+                &picked.replay,
+                picked.runs.clone(),
+                picked.score.clone(),
+                &self.script_path,
+            )),
+            None => Box::new(MenuScreen::with_script(self.script_path.clone())),
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use std::{fs, path::Path};
+
+use raylib::prelude::*;
+
+use crate::{
+    assets::Assets,
+    paths::{resource_path, SCRIPTS_DIRECTORY},
+};
+
+use super::{menu::MenuScreen, Screen};
+
+const VISIBLE_FILES: usize = 12;
+
+/// Lists the Lua files in the scripts folder to pick the script the robots run
+pub struct ScriptsScreen {
+    /// The script that stays selected if the player goes back without picking one
+    script_path: String,
+    files: Vec<String>,
+    selected: usize,
+    picked: bool,
+}
+
+impl ScriptsScreen {
+    pub fn new(script_path: String) -> Self {
+        let mut files: Vec<String> = fs::read_dir(resource_path(SCRIPTS_DIRECTORY))
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().is_some_and(|extension| extension == "lua"))
+                    .map(|path| path.to_string_lossy().into_owned())
+                    .collect()
+            })
+            .unwrap_or_default();
+        files.sort();
+
+        let selected = files
+            .iter()
+            .position(|file| *file == script_path)
+            .unwrap_or(0);
+
+        Self {
+            script_path,
+            files,
+            selected,
+            picked: false,
+        }
+    }
+}
+
+impl Screen for ScriptsScreen {
+    fn initialize(&mut self, _screen_width: f32, _screen_height: f32, _assets: &Assets) {}
+
+    fn update(&mut self, d: &mut RaylibDrawHandle, _assets: &Assets) -> bool {
+        if d.is_key_pressed(KeyboardKey::KEY_BACKSPACE) {
+            return true;
+        }
+
+        if d.is_key_pressed(KeyboardKey::KEY_DOWN) && self.selected + 1 < self.files.len() {
+            self.selected += 1;
+        }
+
+        if d.is_key_pressed(KeyboardKey::KEY_UP) {
+            self.selected = self.selected.saturating_sub(1);
+        }
+
+        // Scrolls just far enough to keep the selected file visible
+        let first = (self.selected + 1).saturating_sub(VISIBLE_FILES);
+
+        let row = (d.get_mouse_y() - 40).div_euclid(24);
+        let hovered = usize::try_from(row)
+            .ok()
+            .map(|row| first + row)
+            .filter(|index| *index < self.files.len() && *index < first + VISIBLE_FILES);
+
+        if let Some(index) = hovered {
+            if d.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
+                self.selected = index;
+                self.picked = true;
+                return true;
+            }
+        }
+
+        if d.is_key_pressed(KeyboardKey::KEY_ENTER) && !self.files.is_empty() {
+            self.picked = true;
+            return true;
+        }
+
+        d.clear_background(Color::BLACK);
+        d.draw_text("Scripts", 4, 4, 24, Color::WHITE);
+
+        if self.files.is_empty() {
+            d.draw_text(
+                &format!("No .lua files in ./{}", SCRIPTS_DIRECTORY),
+                4,
+                40,
+                16,
+                Color::GRAY,
+            );
+        }
+
+        for (i, file) in self
+            .files
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(VISIBLE_FILES)
+        {
+            let name = Path::new(file)
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy();
+
+            d.draw_text(
+                &name,
+                4,
+                40 + (i - first) as i32 * 24,
+                20,
+                match (i == self.selected, Some(i) == hovered) {
+                    (true, _) => Color::YELLOW,
+                    (false, true) => Color::WHITE,
+                    (false, false) => Color::GRAY,
+                },
+            );
+        }
+
+        d.draw_text(
+            "[Enter] Pick [Backspace] Back",
+            4,
+            d.get_screen_height() - 22,
+            16,
+            Color::WHITE,
+        );
+
+        false
+    }
+
+    fn get_new_screen(&self) -> Box<dyn Screen> {
+        Box::new(MenuScreen::with_script(match self.picked {
+            true => self.files[self.selected].clone(),
+            false => self.script_path.clone(),
+        }))
+    }
+}
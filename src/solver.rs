@@ -0,0 +1,99 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::{
+    level::Objective,
+    robot::{Robot, RobotCommand, RobotResponse},
+    team::{Team, Trace},
+    world::World,
+};
+
+/// How many states the search visits before it gives up. Levels with several robots, movers
+/// and boxes have too many states to search them all
+const MAX_NODES: usize = 200_000;
+
+// Interactive tiles change the world, so it is part of the state as well
+type State = (Team, World);
+
+/// The index of the node a node was reached from and the command that was used. Only the
+/// states waiting in the queue are kept besides the visited ones
+type Node = Option<(usize, (usize, RobotCommand))>;
+
+pub enum Solution {
+    /// The shortest sequence of commands that wins the level
+    Found(Trace),
+    /// No sequence of commands wins the level
+    Impossible,
+    /// The search gave up before it found a solution
+    Unknown,
+}
+
+/// Finds the shortest sequence of commands that takes every robot to an exit with every objective
+/// met, turns count as steps. Gives up after visiting `MAX_NODES` states
+pub fn solve(world: &World, robots: &[Robot], objectives: &[Objective]) -> Solution {
+    let team = Team::new(robots.to_vec(), objectives.to_vec(), world);
+    if team.is_won() {
+        return Solution::Found(vec![]);
+    }
+
+    let mut visited: HashSet<State> = HashSet::from([(team.clone(), world.clone())]);
+    let mut nodes: Vec<Node> = vec![None];
+    let mut queue = VecDeque::from([(0, (team, world.clone()))]);
+
+    while let Some((index, (node_team, node_world))) = queue.pop_front() {
+        for robot in 0..robots.len() {
+            if node_team.finished[robot] {
+                continue;
+            }
+
+            for command in [
+                RobotCommand::Forward,
+                RobotCommand::Left,
+                RobotCommand::Right,
+                RobotCommand::PickUp,
+                RobotCommand::Use,
+            ] {
+                let mut team = node_team.clone();
+                let mut world = node_world.clone();
+
+                match team.execute(robot, command, &mut world) {
+                    RobotResponse::Error(_) | RobotResponse::Action(false) => continue,
+                    _ => (),
+                }
+
+                let state = (team, world);
+                if visited.contains(&state) {
+                    continue;
+                }
+
+                if nodes.len() >= MAX_NODES {
+                    return Solution::Unknown;
+                }
+
+                nodes.push(Some((index, (robot, command))));
+
+                if state.0.is_won() {
+                    return Solution::Found(reconstruct(&nodes, nodes.len() - 1));
+                }
+
+                visited.insert(state.clone());
+                queue.push_back((nodes.len() - 1, state));
+            }
+        }
+    }
+
+    Solution::Impossible
+}
+
+fn reconstruct(nodes: &[Node], end: usize) -> Trace {
+    let mut trace = vec![];
+    let mut index = end;
+
+    while let Some((parent, step)) = nodes[index] {
+        trace.push(step);
+        index = parent;
+    }
+
+    trace.reverse();
+
+    trace
+}
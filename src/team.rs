@@ -0,0 +1,106 @@
+use crate::{
+    level::{has_finished, Objective},
+    robot::{Robot, RobotCommand, RobotError, RobotResponse},
+    world::{dynamic::MoverKind, World},
+};
+
+/// The commands of every robot in the order they were run, as the index of the robot and its
+/// command
+pub type Trace = Vec<(usize, RobotCommand)>;
+
+/// The robots of a variant playing together. Robots block each other and leave the world once
+/// they finished. The world ticks once after every command of any robot
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Team {
+    pub robots: Vec<Robot>,
+    pub finished: Vec<bool>,
+    objectives: Vec<Objective>,
+}
+
+impl Team {
+    pub fn new(robots: Vec<Robot>, objectives: Vec<Objective>, world: &World) -> Self {
+        let mut team = Self {
+            finished: vec![false; robots.len()],
+            robots,
+            objectives,
+        };
+        team.update_finished(world);
+
+        team
+    }
+
+    /// The positions of the robots that are still in the world
+    fn positions(&self) -> Vec<(u8, u8)> {
+        self.robots
+            .iter()
+            .zip(&self.finished)
+            .filter(|(_, finished)| !**finished)
+            .map(|(robot, _)| (robot.x, robot.y))
+            .collect()
+    }
+
+    /// The positions of everything blocking the way of the robot with the given index
+    fn others(&self, index: usize, world: &World) -> Vec<(u8, u8)> {
+        let robot = &self.robots[index];
+
+        self.positions()
+            .into_iter()
+            .filter(|position| *position != (robot.x, robot.y))
+            .chain(world.movers.iter().map(|mover| (mover.x, mover.y)))
+            .collect()
+    }
+
+    /// Runs a command for one robot, responds with `Finished` once the robot reached an exit
+    pub fn execute(
+        &mut self,
+        index: usize,
+        command: RobotCommand,
+        world: &mut World,
+    ) -> RobotResponse {
+        if self.finished[index] {
+            return RobotResponse::Finished;
+        }
+
+        let others = self.others(index, world);
+        let response = self.robots[index].execute(command, world, &others);
+
+        if let RobotResponse::Error(_) = response {
+            return response;
+        }
+
+        // Meeting the last objective can finish robots that are already waiting on an exit
+        self.update_finished(world);
+
+        let positions = self.positions();
+        world.tick(&positions);
+
+        // Every robot is checked after the tick, losing any of them loses the variant, so it
+        // is reported to whichever robot is running and the simulation stops there
+        let caught = positions.into_iter().find(|position| {
+            world
+                .mover_at(*position)
+                .is_some_and(|mover| mover.kind == MoverKind::Enemy)
+        });
+        if let Some((x, y)) = caught {
+            return RobotResponse::Error(RobotError::Caught(x, y));
+        }
+
+        match self.finished[index] {
+            true => RobotResponse::Finished,
+            false => response,
+        }
+    }
+
+    fn update_finished(&mut self, world: &World) {
+        for index in 0..self.robots.len() {
+            if !self.finished[index] {
+                self.finished[index] = has_finished(&self.objectives, &self.robots, index, world);
+            }
+        }
+    }
+
+    /// Whether every robot reached an exit
+    pub fn is_won(&self) -> bool {
+        self.finished.iter().all(|finished| *finished)
+    }
+}
@@ -0,0 +1,175 @@
+use std::{collections::HashMap, fmt, fs, io, path::Path};
+
+use raylib::{
+    math::Rectangle,
+    texture::{Image, Texture2D},
+    RaylibHandle, RaylibThread,
+};
+
+use crate::assets::TextureKey;
+
+// A tileset file names a sprite sheet next to it and the size of its sprites in pixels, followed
+// by the column and row of the sprite of every tile it changes. Tiles are named like their
+// textures in the `tiles` folder. `autotile` lines place 16 sprites in a row instead, picked by
+// which neighbours are the same tile, adding 1 for the one above, 2 to the right, 4 below and 8
+// to the left. Tiles the tileset has no sprite for keep their own texture:
+//
+// sheet desert.png
+// size 16
+// ground 0 0
+// exit 1 0
+// autotile wall 0 1
+
+#[derive(Debug)]
+pub enum TilesetError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+    Sheet(String),
+}
+
+impl fmt::Display for TilesetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TilesetError::Io(error) => write!(f, "{}", error),
+            TilesetError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            TilesetError::Sheet(error) => write!(f, "could not load the sheet: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for TilesetError {}
+
+fn parse_error<T>(line: usize, message: impl ToString) -> Result<T, TilesetError> {
+    Err(TilesetError::Parse {
+        line,
+        message: message.to_string(),
+    })
+}
+
+/// Parses the name of a tile followed by the column and row of its sprite
+fn parse_cell<'a>(
+    line: usize,
+    name: Option<&str>,
+    mut words: impl Iterator<Item = &'a str>,
+) -> Result<(TextureKey, (u16, u16)), TilesetError> {
+    let key = match name {
+        Some(name) => match TextureKey::from_tile_name(name) {
+            Some(key) => key,
+            None => return parse_error(line, format!("unknown tile {}", name)),
+        },
+        None => return parse_error(line, "expected a tile"),
+    };
+
+    match (words.next().map(str::parse), words.next().map(str::parse)) {
+        (Some(Ok(column)), Some(Ok(row))) => Ok((key, (column, row))),
+        _ => parse_error(line, "expected a column and a row"),
+    }
+}
+
+/// Sprites from one sheet that replace the textures of the tiles, so a level can have its own
+/// look
+pub struct Tileset {
+    sheet: Texture2D,
+    tile_size: u16,
+    sprites: HashMap<TextureKey, (u16, u16)>,
+    /// The first of the 16 sprites of every tile that connects to its neighbours
+    autotiles: HashMap<TextureKey, (u16, u16)>,
+}
+
+impl Tileset {
+    pub fn load(
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        path: &Path,
+    ) -> Result<Tileset, TilesetError> {
+        let source = fs::read_to_string(path).map_err(TilesetError::Io)?;
+
+        // The sheet is relative to the tileset file
+        Tileset::from_source(rl, thread, &source, |sheet| {
+            fs::read(path.with_file_name(sheet))
+        })
+    }
+
+    /// Creates a tileset from the contents of a tileset file
+    ///
+    /// * `read_sheet`: Reads the PNG file of the sheet the tileset names
+    pub fn from_source(
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        source: &str,
+        read_sheet: impl FnOnce(&str) -> io::Result<Vec<u8>>,
+    ) -> Result<Tileset, TilesetError> {
+        let mut sheet = None;
+        let mut tile_size = None;
+        let mut sprites = HashMap::new();
+        let mut autotiles = HashMap::new();
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let mut words = line.split_whitespace();
+
+            match words.next() {
+                None => continue,
+                Some("sheet") => match words.next() {
+                    Some(file) => sheet = Some(file),
+                    None => return parse_error(line_number, "expected a file"),
+                },
+                Some("size") => match words.next().map(str::parse) {
+                    Some(Ok(size)) if size > 0 => tile_size = Some(size),
+                    _ => return parse_error(line_number, "expected a size above 0"),
+                },
+                Some("autotile") => {
+                    let (key, cell) = parse_cell(line_number, words.next(), words)?;
+                    autotiles.insert(key, cell);
+                }
+                Some(name) => {
+                    let (key, cell) = parse_cell(line_number, Some(name), words)?;
+                    sprites.insert(key, cell);
+                }
+            }
+        }
+
+        let Some(sheet) = sheet else {
+            return parse_error(1, "tileset has no sheet");
+        };
+        let Some(tile_size) = tile_size else {
+            return parse_error(1, "tileset has no size");
+        };
+
+        let bytes = read_sheet(sheet).map_err(TilesetError::Io)?;
+        let image = Image::load_image_from_mem(".png", &bytes).map_err(TilesetError::Sheet)?;
+        let sheet = rl
+            .load_texture_from_image(thread, &image)
+            .map_err(TilesetError::Sheet)?;
+
+        Ok(Tileset {
+            sheet,
+            tile_size,
+            sprites,
+            autotiles,
+        })
+    }
+
+    pub fn sheet(&self) -> &Texture2D {
+        &self.sheet
+    }
+
+    /// The area of the sheet a tile is drawn with, if the tileset has a sprite for it
+    ///
+    /// * `connections`: Which neighbours are the same tile, 1 above, 2 right, 4 below, 8 left
+    pub fn source(&self, key: TextureKey, connections: u8) -> Option<Rectangle> {
+        let (column, row) = match self.autotiles.get(&key) {
+            Some((column, row)) => (column + connections as u16, *row),
+            None => *self.sprites.get(&key)?,
+        };
+
+        let size = self.tile_size as f32;
+
+        Some(Rectangle {
+            x: column as f32 * size,
+            y: row as f32 * size,
+            width: size,
+            height: size,
+        })
+    }
+}
@@ -0,0 +1,121 @@
+use raylib::prelude::*;
+
+use crate::{
+    assets::{Assets, TextureKey},
+    rendering::Drawable,
+    robot::Direction,
+};
+
+use super::{World, WorldTile};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MoverKind {
+    /// Destroys robots it moves onto
+    Enemy,
+    /// Blocks the way, but never moves onto a robot
+    Block,
+}
+
+/// Something that moves one tile forward on every tick and turns around when it is blocked
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Mover {
+    pub x: u8,
+    pub y: u8,
+    pub kind: MoverKind,
+    pub facing: Direction,
+}
+
+impl Mover {
+    pub fn new(x: u8, y: u8, kind: MoverKind, facing: Direction) -> Self {
+        Self { x, y, kind, facing }
+    }
+}
+
+impl Drawable for Mover {
+    fn draw(&self, position: (i32, i32), d: &mut RaylibDrawHandle, assets: &Assets) {
+        match self.kind {
+            MoverKind::Enemy => d.draw_texture_pro(
+                assets.texture(TextureKey::Enemy),
+                Rectangle {
+                    x: 0.0,
+                    y: 0.0,
+                    width: 32.0,
+                    height: 32.0,
+                },
+                Rectangle {
+                    x: position.0 as f32 + 16.0,
+                    y: position.1 as f32 + 16.0,
+                    width: 32.0,
+                    height: 32.0,
+                },
+                Vector2 { x: 16.0, y: 16.0 },
+                self.facing.get_draw_rotation(),
+                Color::WHITE,
+            ),
+            MoverKind::Block => d.draw_texture(
+                assets.texture(TextureKey::Block),
+                position.0,
+                position.1,
+                Color::WHITE,
+            ),
+        }
+    }
+}
+
+/// Toggles every gate after a fixed number of ticks
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GateTimer {
+    pub period: u8,
+    elapsed: u8,
+}
+
+impl GateTimer {
+    pub fn new(period: u8) -> Self {
+        Self { period, elapsed: 0 }
+    }
+}
+
+impl World {
+    /// Advances the world by one tick, which happens after every command of a robot. The gate
+    /// timer runs first, then every mover takes a step in the order they were added, so the
+    /// same commands always lead to the same world
+    ///
+    /// * `robots`: The positions of the robots in the world
+    pub fn tick(&mut self, robots: &[(u8, u8)]) {
+        if let Some(timer) = &mut self.gate_timer {
+            timer.elapsed += 1;
+
+            if timer.elapsed >= timer.period {
+                timer.elapsed = 0;
+                self.toggle_gates();
+            }
+        }
+
+        for index in 0..self.movers.len() {
+            let mover = self.movers[index];
+
+            let target = self
+                .neighbour((mover.x, mover.y), mover.facing)
+                .filter(|position| {
+                    !self.get_tile(*position).collision()
+                        && !self.has_box(*position)
+                        && self.mover_at(*position).is_none()
+                        && (mover.kind == MoverKind::Enemy || !robots.contains(position))
+                });
+
+            match target {
+                Some(position) => {
+                    self.movers[index].x = position.0;
+                    self.movers[index].y = position.1;
+                }
+                None => self.movers[index].facing = mover.facing.opposite(),
+            }
+        }
+    }
+
+    pub fn mover_at(&self, position: (u8, u8)) -> Option<&Mover> {
+        self.movers
+            .iter()
+            .find(|mover| (mover.x, mover.y) == position)
+    }
+}
@@ -0,0 +1,383 @@
+use std::collections::VecDeque;
+
+use crate::{
+    robot::{Direction, Robot},
+    solver::{solve, Solution},
+};
+
+use super::{Tile, World};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MazeStyle {
+    /// A maze without loops where every cell can be reached in exactly one way
+    #[default]
+    Perfect,
+    /// Rectangular rooms connected by corridors
+    Rooms,
+    /// An open field with scattered obstacles
+    Open,
+}
+
+impl MazeStyle {
+    pub const ALL: [MazeStyle; 3] = [MazeStyle::Perfect, MazeStyle::Rooms, MazeStyle::Open];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MazeStyle::Perfect => "Perfect",
+            MazeStyle::Rooms => "Rooms",
+            MazeStyle::Open => "Open",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct GeneratorConfig {
+    /// The size of the world including its border, at least 5x5
+    pub resolution: (u8, u8),
+    /// How much of the world should be walls, from 0.0 to 1.0. Perfect mazes ignore this
+    pub density: f32,
+    pub style: MazeStyle,
+    pub seed: u64,
+}
+
+/// A small deterministic random number generator (SplitMix64), so the same seed always produces
+/// the same world
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `min..max`
+    pub fn range(&mut self, min: usize, max: usize) -> usize {
+        if max <= min {
+            return min;
+        }
+
+        min + (self.next_u64() % (max - min) as u64) as usize
+    }
+
+    pub fn chance(&mut self, probability: f32) -> bool {
+        let value = (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32;
+
+        value < probability
+    }
+}
+
+struct Grid {
+    width: usize,
+    height: usize,
+    walls: Vec<bool>,
+}
+
+impl Grid {
+    fn new(width: usize, height: usize, wall: bool) -> Self {
+        let mut grid = Self {
+            width,
+            height,
+            walls: vec![wall; width * height],
+        };
+
+        for x in 0..width {
+            grid.set_wall((x, 0), true);
+            grid.set_wall((x, height - 1), true);
+        }
+
+        for y in 0..height {
+            grid.set_wall((0, y), true);
+            grid.set_wall((width - 1, y), true);
+        }
+
+        grid
+    }
+
+    fn is_wall(&self, position: (usize, usize)) -> bool {
+        self.walls[position.1 * self.width + position.0]
+    }
+
+    fn set_wall(&mut self, position: (usize, usize), wall: bool) {
+        self.walls[position.1 * self.width + position.0] = wall;
+    }
+
+    fn is_interior(&self, position: (usize, usize)) -> bool {
+        position.0 > 0
+            && position.1 > 0
+            && position.0 < self.width - 1
+            && position.1 < self.height - 1
+    }
+
+    fn open_cells(&self) -> Vec<(usize, usize)> {
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .filter(|position| !self.is_wall(*position))
+            .collect()
+    }
+
+    /// Breadth-first distances from `start` to every reachable cell
+    fn distances(&self, start: (usize, usize)) -> Vec<Option<usize>> {
+        let mut distances = vec![None; self.width * self.height];
+        let mut queue = VecDeque::from([start]);
+        distances[start.1 * self.width + start.0] = Some(0);
+
+        while let Some((x, y)) = queue.pop_front() {
+            let distance = distances[y * self.width + x].unwrap();
+
+            for neighbor in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                let index = neighbor.1 * self.width + neighbor.0;
+
+                if !self.is_wall(neighbor) && distances[index].is_none() {
+                    distances[index] = Some(distance + 1);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        distances
+    }
+}
+
+/// Generates a world and a robot start position from which the exit can always be reached
+pub fn generate(config: &GeneratorConfig) -> (World, Robot) {
+    let (width, height) = (
+        config.resolution.0.max(5) as usize,
+        config.resolution.1.max(5) as usize,
+    );
+    let density = config.density.clamp(0.0, 1.0);
+    let mut rng = Rng::new(config.seed);
+
+    let (grid, start, exit) = match config.style {
+        MazeStyle::Perfect => farthest_exit(perfect_maze(width, height, &mut rng), &mut rng),
+        MazeStyle::Rooms => farthest_exit(rooms(width, height, density, &mut rng), &mut rng),
+        MazeStyle::Open => open_field(width, height, density, &mut rng),
+    };
+
+    // The grid has the size of the world, so every tile fits
+    let mut world = World::new((width as u8, height as u8));
+    for y in 0..height {
+        for x in 0..width {
+            if grid.is_wall((x, y)) {
+                world.set_tile((x as u8, y as u8), Tile::Wall).unwrap();
+            }
+        }
+    }
+    world
+        .set_tile((exit.0 as u8, exit.1 as u8), Tile::Exit)
+        .unwrap();
+
+    let mut directions = [
+        Direction::Right,
+        Direction::Down,
+        Direction::Left,
+        Direction::Up,
+    ];
+    let offset = rng.range(0, directions.len());
+    directions.rotate_left(offset);
+
+    // Face an open tile if there is one, so the first move is not always a turn
+    let facing = directions
+        .into_iter()
+        .find(|direction| {
+            let (x, y) = start;
+            let next = match direction {
+                Direction::Left => (x - 1, y),
+                Direction::Right => (x + 1, y),
+                Direction::Up => (x, y - 1),
+                Direction::Down => (x, y + 1),
+            };
+
+            !grid.is_wall(next)
+        })
+        .unwrap_or(directions[0]);
+
+    let robot = Robot::new(start.0 as u8, start.1 as u8, facing);
+    debug_assert!(!matches!(
+        solve(&world, &[robot], &[]),
+        Solution::Impossible
+    ));
+
+    (world, robot)
+}
+
+/// Picks a random start and places the exit at the reachable cell farthest away from it
+fn farthest_exit(grid: Grid, rng: &mut Rng) -> (Grid, (usize, usize), (usize, usize)) {
+    let cells = grid.open_cells();
+    let start = cells[rng.range(0, cells.len())];
+    let distances = grid.distances(start);
+
+    let exit = cells
+        .iter()
+        .copied()
+        .max_by_key(|(x, y)| distances[y * grid.width + x])
+        .unwrap_or(start);
+
+    (grid, start, exit)
+}
+
+fn perfect_maze(width: usize, height: usize, rng: &mut Rng) -> Grid {
+    let mut grid = Grid::new(width, height, true);
+
+    // Cells sit on odd coordinates, the even ones in between are the walls that get carved away
+    let mut stack: Vec<(usize, usize)> = vec![(1, 1)];
+    grid.set_wall((1, 1), false);
+
+    while let Some(&(x, y)) = stack.last() {
+        let neighbors: Vec<(usize, usize)> = [
+            (x.wrapping_sub(2), y),
+            (x + 2, y),
+            (x, y.wrapping_sub(2)),
+            (x, y + 2),
+        ]
+        .into_iter()
+        .filter(|neighbor| grid.is_interior(*neighbor) && grid.is_wall(*neighbor))
+        .collect();
+
+        if neighbors.is_empty() {
+            stack.pop();
+            continue;
+        }
+
+        let next = neighbors[rng.range(0, neighbors.len())];
+        grid.set_wall(((x + next.0) / 2, (y + next.1) / 2), false);
+        grid.set_wall(next, false);
+        stack.push(next);
+    }
+
+    grid
+}
+
+fn rooms(width: usize, height: usize, density: f32, rng: &mut Rng) -> Grid {
+    let mut grid = Grid::new(width, height, true);
+    let interior = (width - 2) * (height - 2);
+    let max_room_size = ((width.min(height) - 2) / 2).max(2);
+
+    let mut previous_center: Option<(usize, usize)> = None;
+    let mut open = 0;
+
+    for _ in 0..64 {
+        if previous_center.is_some() && open as f32 >= interior as f32 * (1.0 - density) {
+            break;
+        }
+
+        let room_width = rng.range(2, max_room_size + 1).min(width - 2);
+        let room_height = rng.range(2, max_room_size + 1).min(height - 2);
+        let x = rng.range(1, width - 1 - room_width + 1);
+        let y = rng.range(1, height - 1 - room_height + 1);
+
+        for room_y in y..y + room_height {
+            for room_x in x..x + room_width {
+                if grid.is_wall((room_x, room_y)) {
+                    grid.set_wall((room_x, room_y), false);
+                    open += 1;
+                }
+            }
+        }
+
+        let center = (x + room_width / 2, y + room_height / 2);
+        if let Some(previous) = previous_center {
+            open += carve_corridor(&mut grid, previous, center, rng);
+        }
+
+        previous_center = Some(center);
+    }
+
+    grid
+}
+
+fn open_field(
+    width: usize,
+    height: usize,
+    density: f32,
+    rng: &mut Rng,
+) -> (Grid, (usize, usize), (usize, usize)) {
+    let mut grid = Grid::new(width, height, false);
+
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            if rng.chance(density) {
+                grid.set_wall((x, y), true);
+            }
+        }
+    }
+
+    // Start on the left and exit on the right so the robot has to cross the field
+    let third = ((width - 2) / 3).max(1);
+    let start = (rng.range(1, 1 + third), rng.range(1, height - 1));
+    let exit = (
+        rng.range(width - 1 - third, width - 1),
+        rng.range(1, height - 1),
+    );
+
+    grid.set_wall(start, false);
+    grid.set_wall(exit, false);
+
+    if grid.distances(start)[exit.1 * width + exit.0].is_none() {
+        carve_corridor(&mut grid, start, exit, rng);
+    }
+
+    (grid, start, exit)
+}
+
+/// Carves an L-shaped corridor between two cells, returns how many walls were removed
+fn carve_corridor(
+    grid: &mut Grid,
+    from: (usize, usize),
+    to: (usize, usize),
+    rng: &mut Rng,
+) -> usize {
+    let corner = match rng.chance(0.5) {
+        true => (to.0, from.1),
+        false => (from.0, to.1),
+    };
+
+    let mut carved = 0;
+    for (start, end) in [(from, corner), (corner, to)] {
+        for x in start.0.min(end.0)..=start.0.max(end.0) {
+            for y in start.1.min(end.1)..=start.1.max(end.1) {
+                if grid.is_wall((x, y)) {
+                    grid.set_wall((x, y), false);
+                    carved += 1;
+                }
+            }
+        }
+    }
+
+    carved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_style_is_closed_and_solvable() {
+        for style in MazeStyle::ALL {
+            for seed in 0..20 {
+                let (world, robot) = generate(&GeneratorConfig {
+                    resolution: (11, 11),
+                    density: 0.3,
+                    style,
+                    seed,
+                });
+
+                assert!(world.has_closed_border(), "{:?} {}", style, seed);
+                assert!(
+                    matches!(solve(&world, &[robot], &[]), Solution::Found(_)),
+                    "{:?} {}",
+                    style,
+                    seed
+                );
+            }
+        }
+    }
+}
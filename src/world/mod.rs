@@ -0,0 +1,636 @@
+use raylib::prelude::*;
+
+use std::fmt;
+
+use crate::{
+    app::tile_to_screen_pos,
+    assets::{Assets, TextureKey},
+    rendering::Drawable,
+    robot::Direction,
+    tileset::Tileset,
+};
+
+use dynamic::{GateTimer, Mover};
+
+pub mod dynamic;
+pub mod generator;
+
+/// A change to the world caused by the robot interacting with a tile
+#[derive(Clone, Copy, Debug)]
+pub enum TileEvent {
+    /// Replaces the tile the event happened on
+    Replace(Tile),
+    /// Opens every closed gate and closes every open one
+    ToggleGates,
+}
+
+pub trait WorldTile {
+    fn collision(&self) -> bool;
+
+    /// Called when the robot moves onto the tile
+    fn on_enter(&self) -> Option<TileEvent> {
+        None
+    }
+
+    /// Called when the robot moves off the tile
+    fn on_leave(&self) -> Option<TileEvent> {
+        None
+    }
+
+    /// Called when the robot picks up what is lying on the tile, returns the tile left behind
+    fn on_pick_up(&self) -> Option<Tile> {
+        None
+    }
+
+    /// Called when the robot uses the tile in front of it while carrying `keys` keys. Returns
+    /// the event and whether a key was used up
+    fn on_use(&self, _keys: u8) -> Option<(TileEvent, bool)> {
+        None
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Tile {
+    Ground,
+    Exit,
+    Wall,
+    /// Locked doors are opened by using them with a key
+    Door {
+        locked: bool,
+    },
+    Key,
+    /// A lever on a wall that toggles every gate when used
+    Switch,
+    Gate {
+        open: bool,
+    },
+    /// Toggles every gate when the robot steps on or off it
+    PressurePlate,
+    /// Destroys the robot
+    Water,
+    /// Destroys the robot
+    Lava,
+    /// Slides the robot forward until it hits something
+    Ice,
+    /// Moves the robot in the given direction
+    Conveyor(Direction),
+    /// Recharges the battery of the robot
+    Charger,
+    /// Collected as soon as the robot drives over it
+    Gem,
+    /// Numbered from 1 to 9, counts when visited in order
+    Checkpoint(u8),
+    /// Where boxes have to be pushed to
+    Goal,
+}
+
+impl Tile {
+    /// Whether a robot can start a level on the tile, which leaves out tiles that would end
+    /// the run before the first command
+    pub fn can_start_on(&self) -> bool {
+        !self.collision() && !matches!(self, Tile::Water | Tile::Lava | Tile::Exit)
+    }
+
+    /// The character representing the tile in level files
+    pub fn to_char(&self) -> char {
+        match self {
+            Tile::Ground => '.',
+            Tile::Exit => 'E',
+            Tile::Wall => '#',
+            Tile::Door { locked: true } => 'D',
+            Tile::Door { locked: false } => 'd',
+            Tile::Key => 'k',
+            Tile::Switch => 's',
+            Tile::Gate { open: false } => 'G',
+            Tile::Gate { open: true } => 'g',
+            Tile::PressurePlate => 'p',
+            Tile::Water => '~',
+            Tile::Lava => 'L',
+            Tile::Ice => 'i',
+            Tile::Conveyor(Direction::Left) => '<',
+            Tile::Conveyor(Direction::Right) => '>',
+            Tile::Conveyor(Direction::Up) => '^',
+            Tile::Conveyor(Direction::Down) => 'v',
+            Tile::Charger => 'c',
+            Tile::Gem => '*',
+            Tile::Checkpoint(number) => char::from_digit(*number as u32, 10).unwrap_or('1'),
+            Tile::Goal => 'o',
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<Tile> {
+        match c {
+            '.' => Some(Tile::Ground),
+            'E' => Some(Tile::Exit),
+            '#' => Some(Tile::Wall),
+            'D' => Some(Tile::Door { locked: true }),
+            'd' => Some(Tile::Door { locked: false }),
+            'k' => Some(Tile::Key),
+            's' => Some(Tile::Switch),
+            'G' => Some(Tile::Gate { open: false }),
+            'g' => Some(Tile::Gate { open: true }),
+            'p' => Some(Tile::PressurePlate),
+            '~' => Some(Tile::Water),
+            'L' => Some(Tile::Lava),
+            'i' => Some(Tile::Ice),
+            '<' => Some(Tile::Conveyor(Direction::Left)),
+            '>' => Some(Tile::Conveyor(Direction::Right)),
+            '^' => Some(Tile::Conveyor(Direction::Up)),
+            'v' => Some(Tile::Conveyor(Direction::Down)),
+            'c' => Some(Tile::Charger),
+            '*' => Some(Tile::Gem),
+            '1'..='9' => c.to_digit(10).map(|number| Tile::Checkpoint(number as u8)),
+            'o' => Some(Tile::Goal),
+            _ => None,
+        }
+    }
+}
+
+impl WorldTile for Tile {
+    fn collision(&self) -> bool {
+        match self {
+            Tile::Ground => false,
+            Tile::Exit => false,
+            Tile::Wall => true,
+            Tile::Door { locked } => *locked,
+            Tile::Key => false,
+            Tile::Switch => true,
+            Tile::Gate { open } => !open,
+            Tile::PressurePlate => false,
+            Tile::Water => false,
+            Tile::Lava => false,
+            Tile::Ice => false,
+            Tile::Conveyor(_) => false,
+            Tile::Charger => false,
+            Tile::Gem => false,
+            Tile::Checkpoint(_) => false,
+            Tile::Goal => false,
+        }
+    }
+
+    fn on_enter(&self) -> Option<TileEvent> {
+        match self {
+            Tile::PressurePlate => Some(TileEvent::ToggleGates),
+            _ => None,
+        }
+    }
+
+    fn on_leave(&self) -> Option<TileEvent> {
+        match self {
+            Tile::PressurePlate => Some(TileEvent::ToggleGates),
+            _ => None,
+        }
+    }
+
+    fn on_pick_up(&self) -> Option<Tile> {
+        match self {
+            Tile::Key | Tile::Gem => Some(Tile::Ground),
+            _ => None,
+        }
+    }
+
+    fn on_use(&self, keys: u8) -> Option<(TileEvent, bool)> {
+        match self {
+            Tile::Door { locked: true } if keys > 0 => {
+                Some((TileEvent::Replace(Tile::Door { locked: false }), true))
+            }
+            Tile::Switch => Some((TileEvent::ToggleGates, false)),
+            _ => None,
+        }
+    }
+}
+
+impl Tile {
+    pub fn texture_key(&self) -> TextureKey {
+        match self {
+            Tile::Ground => TextureKey::Ground,
+            Tile::Exit => TextureKey::Exit,
+            Tile::Wall => TextureKey::Wall,
+            Tile::Door { locked: true } => TextureKey::DoorLocked,
+            Tile::Door { locked: false } => TextureKey::DoorOpen,
+            Tile::Key => TextureKey::Key,
+            Tile::Switch => TextureKey::Switch,
+            Tile::Gate { open: false } => TextureKey::GateClosed,
+            Tile::Gate { open: true } => TextureKey::GateOpen,
+            Tile::PressurePlate => TextureKey::PressurePlate,
+            Tile::Water => TextureKey::Water,
+            Tile::Lava => TextureKey::Lava,
+            Tile::Ice => TextureKey::Ice,
+            Tile::Conveyor(_) => TextureKey::Conveyor,
+            Tile::Charger => TextureKey::Charger,
+            Tile::Gem => TextureKey::Gem,
+            Tile::Checkpoint(_) => TextureKey::Checkpoint,
+            Tile::Goal => TextureKey::Goal,
+        }
+    }
+
+    /// Draws the tile with an area of a texture, scaled to the size of a tile
+    fn draw_sprite(
+        &self,
+        position: (i32, i32),
+        d: &mut RaylibDrawHandle,
+        texture: &Texture2D,
+        source: Rectangle,
+    ) {
+        // Conveyors share one sprite that is rotated to point in their direction
+        let rotation = match self {
+            Tile::Conveyor(direction) => direction.get_draw_rotation(),
+            _ => 0.0,
+        };
+
+        d.draw_texture_pro(
+            texture,
+            source,
+            Rectangle {
+                x: position.0 as f32 + 16.0,
+                y: position.1 as f32 + 16.0,
+                width: 32.0,
+                height: 32.0,
+            },
+            Vector2 { x: 16.0, y: 16.0 },
+            rotation,
+            Color::WHITE,
+        );
+
+        if let Tile::Checkpoint(number) = self {
+            let text = number.to_string();
+            let text_width = d.measure_text(&text, 20);
+            d.draw_text(
+                &text,
+                position.0 + 16 - text_width / 2,
+                position.1 + 6,
+                20,
+                Color::BLACK,
+            );
+        }
+    }
+}
+
+impl Drawable for Tile {
+    fn draw(&self, position: (i32, i32), d: &mut RaylibDrawHandle, assets: &Assets) {
+        let texture = assets.texture(self.texture_key());
+        let source = Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: texture.width as f32,
+            height: texture.height as f32,
+        };
+
+        self.draw_sprite(position, d, texture, source);
+    }
+}
+
+#[derive(Debug)]
+pub enum WorldError {
+    OutOfBounds(u8, u8),
+}
+
+impl fmt::Display for WorldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorldError::OutOfBounds(x, y) => write!(f, "({}, {}) is outside of the world", x, y),
+        }
+    }
+}
+
+impl std::error::Error for WorldError {}
+
+type Result<T> = std::result::Result<T, WorldError>;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct World {
+    pub width: u8,
+    pub height: u8,
+    tiles: Vec<Tile>,
+    /// Marks left by the robots, for every tile
+    marks: Vec<bool>,
+    /// Which tiles a robot visited or scanned, `None` if the whole world is visible
+    explored: Option<Vec<bool>>,
+    /// Enemies and blocks that move on every tick
+    pub movers: Vec<Mover>,
+    /// The positions of the boxes the robots can push around
+    pub boxes: Vec<(u8, u8)>,
+    pub gate_timer: Option<GateTimer>,
+}
+
+// TODO: Make the world include the border instead of this -1 / width / height shit
+impl World {
+    /// Creates a world covered in ground, without any exits
+    pub fn new(resolution: (u8, u8)) -> Self {
+        let tiles = vec![Tile::Ground; resolution.0 as usize * resolution.1 as usize];
+
+        Self {
+            width: resolution.0,
+            height: resolution.1,
+            marks: vec![false; tiles.len()],
+            explored: None,
+            tiles,
+            movers: vec![],
+            boxes: vec![],
+            gate_timer: None,
+        }
+    }
+
+    fn get_tile_index(&self, position: (u8, u8)) -> usize {
+        position.1 as usize * self.width as usize + position.0 as usize
+    }
+
+    pub fn set_tile(&mut self, position: (u8, u8), tile: Tile) -> Result<()> {
+        if position.0 >= self.width || position.1 >= self.height {
+            return Err(WorldError::OutOfBounds(position.0, position.1));
+        }
+
+        let index = self.get_tile_index(position);
+        self.tiles[index] = tile;
+
+        Ok(())
+    }
+
+    pub fn get_tile(&self, position: (u8, u8)) -> Tile {
+        let index = self.get_tile_index(position);
+
+        self.tiles[index]
+    }
+
+    /// Marks are only set on tiles a robot stands on, which are always inside the world
+    pub fn set_marked(&mut self, position: (u8, u8), marked: bool) {
+        let index = self.get_tile_index(position);
+        self.marks[index] = marked;
+    }
+
+    pub fn is_marked(&self, position: (u8, u8)) -> bool {
+        self.marks[self.get_tile_index(position)]
+    }
+
+    /// Covers every tile until a robot visits or scans it
+    pub fn hide_unexplored(&mut self) {
+        self.explored = Some(vec![false; self.tiles.len()]);
+    }
+
+    pub fn reveal(&mut self, position: (u8, u8)) {
+        let index = self.get_tile_index(position);
+
+        if let Some(explored) = &mut self.explored {
+            explored[index] = true;
+        }
+    }
+
+    pub fn is_explored(&self, position: (u8, u8)) -> bool {
+        match &self.explored {
+            Some(explored) => explored[self.get_tile_index(position)],
+            None => true,
+        }
+    }
+
+    /// The position next to `position` in the given direction, `None` at the edge of the world
+    pub fn neighbour(&self, position: (u8, u8), direction: Direction) -> Option<(u8, u8)> {
+        match direction {
+            Direction::Left if position.0 == 0 => None,
+            Direction::Up if position.1 == 0 => None,
+            Direction::Right if position.0 + 1 >= self.width => None,
+            Direction::Down if position.1 + 1 >= self.height => None,
+            _ => Some(direction.offset(position)),
+        }
+    }
+
+    /// Whether a tile is on the edge of the world
+    pub fn is_border(&self, position: (u8, u8)) -> bool {
+        position.0 == 0
+            || position.1 == 0
+            || position.0 + 1 >= self.width
+            || position.1 + 1 >= self.height
+    }
+
+    /// Whether every tile on the edge is a wall or an exit, so no robot can drive out of the world
+    pub fn has_closed_border(&self) -> bool {
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .filter(|position| self.is_border(*position))
+            .all(|position| matches!(self.get_tile(position), Tile::Wall | Tile::Exit))
+    }
+
+    /// Turns every tile on the edge that is not a wall or an exit into a wall and removes the
+    /// movers and boxes on it
+    pub fn close_border(&mut self) {
+        let (width, height) = (self.width, self.height);
+        let inside = |x: u8, y: u8| x > 0 && y > 0 && x + 1 < width && y + 1 < height;
+
+        self.movers.retain(|mover| inside(mover.x, mover.y));
+        self.boxes.retain(|position| inside(position.0, position.1));
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.is_border((x, y)) && !matches!(self.get_tile((x, y)), Tile::Exit) {
+                    let index = self.get_tile_index((x, y));
+                    self.tiles[index] = Tile::Wall;
+                }
+            }
+        }
+    }
+
+    pub fn add_box(&mut self, position: (u8, u8)) -> Result<()> {
+        if position.0 >= self.width || position.1 >= self.height {
+            return Err(WorldError::OutOfBounds(position.0, position.1));
+        }
+
+        self.boxes.push(position);
+
+        Ok(())
+    }
+
+    pub fn has_box(&self, position: (u8, u8)) -> bool {
+        self.boxes.contains(&position)
+    }
+
+    /// Pushes the box at `position` one tile further in the given direction. Returns false and
+    /// leaves the box where it is if a wall, another box or anything in `others` is in the way
+    pub fn push_box(
+        &mut self,
+        position: (u8, u8),
+        direction: Direction,
+        others: &[(u8, u8)],
+    ) -> bool {
+        let target = self.neighbour(position, direction).filter(|target| {
+            !self.get_tile(*target).collision()
+                && !self.has_box(*target)
+                && !others.contains(target)
+        });
+
+        match (target, self.boxes.iter().position(|b| *b == position)) {
+            (Some(target), Some(index)) => {
+                self.boxes[index] = target;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn add_mover(&mut self, mover: Mover) -> Result<()> {
+        if mover.x >= self.width || mover.y >= self.height {
+            return Err(WorldError::OutOfBounds(mover.x, mover.y));
+        }
+
+        self.movers.push(mover);
+
+        Ok(())
+    }
+
+    /// Events only happen on tiles the robot interacts with, which are always inside the world
+    pub fn apply_event(&mut self, position: (u8, u8), event: TileEvent) {
+        match event {
+            TileEvent::Replace(tile) => {
+                let index = self.get_tile_index(position);
+                self.tiles[index] = tile;
+            }
+            TileEvent::ToggleGates => self.toggle_gates(),
+        }
+    }
+
+    fn toggle_gates(&mut self) {
+        for tile in self.tiles.iter_mut() {
+            if let Tile::Gate { open } = tile {
+                *open = !*open;
+            }
+        }
+    }
+
+    pub fn count_tiles(&self, predicate: impl Fn(&Tile) -> bool) -> usize {
+        self.tiles.iter().filter(|tile| predicate(tile)).count()
+    }
+
+    /// The checkpoints ordered by their number, each with the index of its tile
+    fn checkpoints(&self) -> Vec<(u8, usize)> {
+        let mut checkpoints: Vec<(u8, usize)> = self
+            .tiles
+            .iter()
+            .enumerate()
+            .filter_map(|(index, tile)| match tile {
+                Tile::Checkpoint(number) => Some((*number, index)),
+                _ => None,
+            })
+            .collect();
+        checkpoints.sort();
+
+        checkpoints
+    }
+
+    /// Whether the checkpoints are numbered 1 to n, each number once
+    pub fn has_numbered_checkpoints(&self) -> bool {
+        self.checkpoints()
+            .iter()
+            .enumerate()
+            .all(|(i, (number, _))| *number as usize == i + 1)
+    }
+
+    /// Numbers the checkpoints 1 to n again in the same order, after some of them were removed
+    pub fn renumber_checkpoints(&mut self) {
+        for (i, (_, index)) in self.checkpoints().into_iter().enumerate() {
+            self.tiles[index] = Tile::Checkpoint(i as u8 + 1);
+        }
+    }
+
+    /// Returns a copy of the world with a different size, keeping the tiles, movers and boxes
+    /// that still fit
+    pub fn resized(&self, resolution: (u8, u8)) -> World {
+        let mut world = World::new(resolution);
+        world.gate_timer = self.gate_timer;
+
+        for mover in &self.movers {
+            let _ = world.add_mover(*mover);
+        }
+
+        for position in &self.boxes {
+            let _ = world.add_box(*position);
+        }
+
+        for y in 0..resolution.1.min(self.height) {
+            for x in 0..resolution.0.min(self.width) {
+                let index = world.get_tile_index((x, y));
+                world.tiles[index] = self.get_tile((x, y));
+            }
+        }
+
+        world
+    }
+}
+
+impl World {
+    /// Which neighbours of a tile have the same texture, as the sum of 1 above, 2 right, 4 below
+    /// and 8 left. The outside of the world counts as the same, so walls connect to the border
+    fn connections(&self, position: (u8, u8), key: TextureKey) -> u8 {
+        [
+            Direction::Up,
+            Direction::Right,
+            Direction::Down,
+            Direction::Left,
+        ]
+        .into_iter()
+        .enumerate()
+        .filter(|(_, direction)| {
+            self.neighbour(position, *direction)
+                .is_none_or(|neighbour| self.get_tile(neighbour).texture_key() == key)
+        })
+        .map(|(bit, _)| 1 << bit)
+        .sum()
+    }
+
+    /// Draws the world with the sprites of a tileset, tiles it has no sprite for are drawn with
+    /// their own texture
+    pub fn draw_themed(
+        &self,
+        d: &mut RaylibDrawHandle,
+        assets: &Assets,
+        tileset: Option<&Tileset>,
+    ) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let screen_pos = tile_to_screen_pos(x, y);
+                let tile = self.get_tile((x, y));
+                let key = tile.texture_key();
+
+                match tileset.and_then(|tileset| {
+                    let source = tileset.source(key, self.connections((x, y), key))?;
+                    Some((tileset.sheet(), source))
+                }) {
+                    Some((sheet, source)) => tile.draw_sprite(screen_pos, d, sheet, source),
+                    None => tile.draw(screen_pos, d, assets),
+                }
+
+                if self.is_marked((x, y)) {
+                    d.draw_circle(screen_pos.0 + 16, screen_pos.1 + 16, 6.0, Color::ORANGE);
+                }
+            }
+        }
+
+        for (x, y) in &self.boxes {
+            let screen_pos = tile_to_screen_pos(*x, *y);
+
+            d.draw_texture(
+                assets.texture(TextureKey::Box),
+                screen_pos.0,
+                screen_pos.1,
+                Color::WHITE,
+            );
+        }
+
+        for mover in &self.movers {
+            mover.draw(tile_to_screen_pos(mover.x, mover.y), d, assets);
+        }
+
+        // Drawn last so nothing on the hidden tiles shows through
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if !self.is_explored((x, y)) {
+                    let screen_pos = tile_to_screen_pos(x, y);
+                    d.draw_rectangle(screen_pos.0, screen_pos.1, 32, 32, Color::BLACK);
+                }
+            }
+        }
+    }
+}
+
+impl Drawable for World {
+    fn draw(&self, _position: (i32, i32), d: &mut RaylibDrawHandle, assets: &Assets) {
+        self.draw_themed(d, assets, None);
+    }
+}